@@ -1,8 +1,12 @@
+// Web builds (wasm32-unknown-unknown) also need instant with its wasm-bindgen feature, getrandom with its js
+// feature for thread_rng, and web-sys with the Window and Storage features
 use eframe::{egui, epi};
-use rand::{seq::SliceRandom, thread_rng};
-use std::fmt;
+use instant::Instant; // std's Instant panics on wasm32; natively this is std's
+use rand::{rngs::StdRng, seq::SliceRandom, thread_rng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::{fmt, io, time::Duration};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 enum Suit {
     Hearts,
     Diamonds,
@@ -10,7 +14,7 @@ enum Suit {
     Spades,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 enum Value {
     Number(u8), // 2-10
     Jack,
@@ -19,26 +23,50 @@ enum Value {
     Ace,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 struct Card {
     value: Value,
     suit: Suit,
 }
 
+impl Suit {
+    fn symbol(&self) -> char {
+        match self {
+            Suit::Hearts => '♥',
+            Suit::Diamonds => '♦',
+            Suit::Clubs => '♣',
+            Suit::Spades => '♠',
+        }
+    }
+}
+
 impl fmt::Display for Card {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let value_str = match self.value {
+        write!(f, "{} of {:?}", self.rank_str(), self.suit)
+    }
+}
+
+impl Value {
+    fn rank_str(&self) -> String {
+        match self {
             Value::Number(num) => num.to_string(),
             Value::Jack => "J".to_string(),
             Value::Queen => "Q".to_string(),
             Value::King => "K".to_string(),
             Value::Ace => "A".to_string(),
-        };
-        write!(f, "{} of {:?}", value_str, self.suit)
+        }
     }
 }
 
 impl Card {
+    fn rank_str(&self) -> String {
+        self.value.rank_str()
+    }
+
+    fn short(&self) -> String {
+        format!("{}{}", self.rank_str(), self.suit.symbol())
+    }
+
     fn value(&self) -> u8 {
         match self.value {
             Value::Number(num) => num,
@@ -48,224 +76,5007 @@ impl Card {
     }
 }
 
-struct BlackjackApp {
-    deck: Vec<Card>,
-    player_hands: Vec<Vec<Card>>,
-    dealer_hand: Vec<Card>,
-    current_hand: usize,
-    game_state: GameState,
-    player_bets: Vec<usize>,
-    total_money: usize,
+#[derive(Clone, Copy)]
+enum CardView<'a> {
+    FaceUp(&'a Card),
+    FaceDown,
 }
 
-enum GameState {
-    Betting,
-    PlayerTurn,
-    DealerTurn,
-    GameOver(String),
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum CardTheme {
+    Classic,   // Red hearts and diamonds, black clubs and spades
+    FourColor, // Every suit its own color
+    Minimal,   // Plain faces, all in one ink
 }
 
-impl Default for BlackjackApp {
-    fn default() -> Self {
-        Self::new()
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ShoeCountDisplay {
+    Always,
+    BetweenRounds,  // Hidden while a round is being played
+    AfterReshuffle, // Only between rounds, until a second round comes out of a new shoe
+}
+
+impl CardTheme {
+    fn suit_color(&self, suit: Suit) -> egui::Color32 {
+        match (self, suit) {
+            (CardTheme::Minimal, _) => egui::Color32::BLACK,
+            (_, Suit::Hearts) => egui::Color32::RED,
+            (CardTheme::FourColor, Suit::Diamonds) => egui::Color32::from_rgb(0, 90, 200),
+            (CardTheme::FourColor, Suit::Clubs) => egui::Color32::from_rgb(0, 140, 0),
+            (CardTheme::Classic, Suit::Diamonds) => egui::Color32::RED,
+            (_, Suit::Clubs | Suit::Spades) => egui::Color32::BLACK,
+        }
+    }
+
+    fn face_fill(&self) -> egui::Color32 {
+        match self {
+            CardTheme::Minimal => egui::Color32::from_gray(230),
+            _ => egui::Color32::WHITE,
+        }
     }
 }
 
-impl BlackjackApp {
-    fn new() -> Self {
-        let mut app = BlackjackApp {
-            deck: Vec::new(),
-            player_hands: vec![Vec::new()],
-            dealer_hand: Vec::new(),
-            current_hand: 0,
-            game_state: GameState::Betting,
-            player_bets: vec![10], // Initial bet, could be made dynamic
-            total_money: 100, // Starting money
-        };
-        app.new_round();
-        app
+impl CardView<'_> {
+    fn all_face_up(hand: &[Card]) -> Vec<CardView<'_>> {
+        hand.iter().map(CardView::FaceUp).collect()
     }
 
-    fn create_deck() -> Vec<Card> {
-        let suits = [Suit::Hearts, Suit::Diamonds, Suit::Clubs, Suit::Spades];
-        let values = [
-            Value::Number(2), Value::Number(3), Value::Number(4), Value::Number(5), Value::Number(6),
-            Value::Number(7), Value::Number(8), Value::Number(9), Value::Number(10),
-            Value::Jack, Value::Queen, Value::King, Value::Ace,
-        ];
-        let mut deck = Vec::new();
+    fn label(&self) -> String {
+        match self {
+            CardView::FaceUp(card) => card.short(),
+            CardView::FaceDown => String::from("??"),
+        }
+    }
+}
+
+fn hand_to_string(hand: &[Card], value_rules: ValueRules) -> String {
+    let cards = hand.iter().map(|c| c.short()).collect::<Vec<_>>().join(" ");
+    format!("{} ({})", cards, BlackjackApp::calculate_hand_value(hand, value_rules))
+}
+
+struct MoneyFormat {
+    symbol: String,
+    separator: char,
+    display_cap: Option<usize>, // Larger amounts show as "$cap+"
+}
+
+impl Default for MoneyFormat {
+    fn default() -> Self {
+        MoneyFormat {
+            symbol: String::from("$"),
+            separator: ',',
+            display_cap: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+enum LowDeckPolicy {
+    Reshuffle, // Keep playing with the discards shuffled back in
+    VoidRound, // Call the round off and return the bets
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+enum ShuffleKind {
+    Perfect,                    // Uniform Fisher-Yates
+    Riffle { passes: usize },   // Gilbert-Shannon-Reeds riffles
+    Overhand { passes: usize }, // Small packets moved from hand to table
+}
 
-        for &suit in suits.iter() {
-            for &value in values.iter() {
-                deck.push(Card { value, suit });
+impl ShuffleKind {
+    fn apply(&self, deck: &mut Vec<Card>, rng: &mut StdRng) {
+        match *self {
+            ShuffleKind::Perfect => deck.shuffle(rng),
+            ShuffleKind::Riffle { passes } => {
+                for _ in 0..passes {
+                    Self::riffle(deck, rng);
+                }
+            }
+            ShuffleKind::Overhand { passes } => {
+                for _ in 0..passes {
+                    Self::overhand(deck, rng);
+                }
             }
         }
+    }
 
-        deck
+    fn riffle(deck: &mut Vec<Card>, rng: &mut StdRng) {
+        // Cut near the middle, then drop from each half in proportion to its size
+        let cut = (0..deck.len()).filter(|_| rng.gen_bool(0.5)).count();
+        let right = deck.split_off(cut);
+        let left = std::mem::take(deck);
+        let (mut i, mut j) = (0, 0);
+
+        while i < left.len() || j < right.len() {
+            let left_remaining = left.len() - i;
+            let right_remaining = right.len() - j;
+            if rng.gen_range(0..left_remaining + right_remaining) < left_remaining {
+                deck.push(left[i].clone());
+                i += 1;
+            } else {
+                deck.push(right[j].clone());
+                j += 1;
+            }
+        }
     }
 
-    fn can_split(hand: &[Card]) -> bool {
-        hand.len() == 2 && hand[0].value == hand[1].value
+    fn overhand(deck: &mut Vec<Card>, rng: &mut StdRng) {
+        // Packets keep their internal order but land in reverse order on the new pile
+        let mut in_hand = std::mem::take(deck);
+
+        while !in_hand.is_empty() {
+            let packet_size = rng.gen_range(1..=in_hand.len().min(8));
+            let packet = in_hand.split_off(in_hand.len() - packet_size);
+            deck.extend(packet);
+        }
     }
+}
 
-    fn new_round(&mut self) {
-        self.deck = Self::create_deck();
-        self.deck.shuffle(&mut thread_rng());
-        self.player_hands = vec![vec![self.deck.pop().unwrap(), self.deck.pop().unwrap()]];
-        self.dealer_hand = vec![self.deck.pop().unwrap(), self.deck.pop().unwrap()];
-        self.current_hand = 0;
-        self.game_state = GameState::PlayerTurn;
+trait DealerRules {
+    fn should_hit(&self, dealer_hand: &[Card], player_hands: &[Vec<Card>], value_rules: ValueRules) -> bool;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+struct DealerThreshold {
+    stand_hard: usize,
+    stand_soft: usize, // 18 gives the common hit-soft-17 table
+}
+
+impl Default for DealerThreshold {
+    fn default() -> Self {
+        DealerThreshold { stand_hard: 17, stand_soft: 17 }
     }
+}
 
-    fn hit(&mut self) {
-        if let Some(card) = self.deck.pop() {
-            self.player_hands[self.current_hand].push(card);
-            if self.calculate_hand_value(&self.player_hands[self.current_hand]) > 21 {
-                self.stand(); // Move to next hand or dealer's turn
-            }
+impl DealerThreshold {
+    fn stand_at(&self, soft: bool) -> usize {
+        if soft {
+            self.stand_soft
         } else {
-            eprintln!("Deck depleted. Unable to draw more cards.");
+            self.stand_hard
         }
     }
+}
 
-    fn stand(&mut self) {
-        if self.current_hand + 1 < self.player_hands.len() {
-            self.current_hand += 1; // Move to the next hand if any
-        } else {
-            self.game_state = GameState::DealerTurn; // Move to dealer's turn
-            self.dealer_turn();
+struct StandOnThreshold(DealerThreshold);
+
+impl DealerRules for StandOnThreshold {
+    fn should_hit(&self, dealer_hand: &[Card], _player_hands: &[Vec<Card>], value_rules: ValueRules) -> bool {
+        let soft = BlackjackApp::is_soft(dealer_hand, value_rules);
+        BlackjackApp::calculate_hand_value(dealer_hand, value_rules) < self.0.stand_at(soft)
+    }
+}
+
+struct BeatBestHand; // Novelty: dealer ignores 17 and draws until ahead of every live hand
+
+impl DealerRules for BeatBestHand {
+    fn should_hit(&self, dealer_hand: &[Card], player_hands: &[Vec<Card>], value_rules: ValueRules) -> bool {
+        let dealer_value = BlackjackApp::calculate_hand_value(dealer_hand, value_rules);
+        let best_player_value = player_hands
+            .iter()
+            .map(|hand| BlackjackApp::calculate_hand_value(hand, value_rules))
+            .filter(|&value| value <= 21)
+            .max();
+
+        match best_player_value {
+            Some(best) => dealer_value < 21 && dealer_value <= best,
+            None => false, // Every hand busted, nothing to beat
         }
     }
+}
 
-    fn double_down(&mut self) {
-        if self.total_money >= self.player_bets[self.current_hand] {
-            self.total_money -= self.player_bets[self.current_hand];
-            self.player_bets[self.current_hand] *= 2;
-            self.hit();
-            if self.calculate_hand_value(&self.player_hands[self.current_hand]) <= 21 {
-                self.stand();
-            }
-        } else {
-            eprintln!("Insufficient funds to double down.");
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+enum DealerPlay {
+    Standard,
+    BeatBestHand,
+}
+
+impl DealerPlay {
+    fn rules(&self, threshold: DealerThreshold) -> Box<dyn DealerRules> {
+        match self {
+            DealerPlay::Standard => Box::new(StandOnThreshold(threshold)),
+            DealerPlay::BeatBestHand => Box::new(BeatBestHand),
         }
     }
+}
 
-    fn split(&mut self) {
-        if !Self::can_split(&self.player_hands[self.current_hand]) || self.total_money < self.player_bets[self.current_hand] {
-            eprintln!("Cannot split.");
-            return;
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+enum DoubleRule {
+    Any,
+    NineTenEleven, // European tables: hard 9, 10 or 11 only
+    TenEleven,
+}
+
+impl DoubleRule {
+    fn allows(&self, hard_total: usize) -> bool {
+        match self {
+            DoubleRule::Any => true,
+            DoubleRule::NineTenEleven => (9..=11).contains(&hard_total),
+            DoubleRule::TenEleven => (10..=11).contains(&hard_total),
         }
+    }
+}
 
-        let hand_to_split = self.player_hands[self.current_hand].clone();
-        let bet_for_new_hand = self.player_bets[self.current_hand];
+// Gets a live (unbusted) player hand and the dealer's final hand. Some(outcome) replaces the normal
+// result for that hand and is paid as usual; None leaves standard settlement alone. Returning
+// Busted for a live hand fails the settlement consistency check and the round is reset.
+type SettlementHook = Box<dyn Fn(&[Card], &[Card]) -> Option<Outcome>>;
 
-        self.total_money -= bet_for_new_hand;
-        self.player_bets.push(bet_for_new_hand);
+trait HandBonus {
+    fn name(&self) -> String;
+    fn matches(&self, hand: &[Card], split: bool, value_rules: ValueRules) -> bool; // split: the hand came from a split
+    fn pays(&self) -> usize; // Winnings per unit bet, replacing the normal 1:1 on a winning hand
+}
+
+struct SuitedBlackjack;
 
-        let card_for_new_hand = hand_to_split[1];
-        self.player_hands[self.current_hand].pop();
-        self.player_hands[self.current_hand].push(self.deck.pop().unwrap());
-        self.player_hands.push(vec![card_for_new_hand, self.deck.pop().unwrap()]);
+impl HandBonus for SuitedBlackjack {
+    fn name(&self) -> String {
+        String::from("Suited blackjack")
     }
 
-    fn dealer_turn(&mut self) {
-        while self.calculate_hand_value(&self.dealer_hand) < 17 {
-            if let Some(card) = self.deck.pop() {
-                self.dealer_hand.push(card);
-            } else {
-                break; // Dealer stops if deck is depleted
-            }
+    fn matches(&self, hand: &[Card], split: bool, value_rules: ValueRules) -> bool {
+        // Like the natural it dresses up, a two-card 21 after a split doesn't count
+        !split && hand.len() == 2 && hand[0].suit == hand[1].suit && BlackjackApp::calculate_hand_value(hand, value_rules) == 21
+    }
+
+    fn pays(&self) -> usize {
+        2
+    }
+}
+
+struct SuitedSevens;
+
+impl HandBonus for SuitedSevens {
+    fn name(&self) -> String {
+        String::from("Suited 7-7-7")
+    }
+
+    fn matches(&self, hand: &[Card], _split: bool, _value_rules: ValueRules) -> bool {
+        hand.len() == 3 && hand.iter().all(|c| c.value == Value::Number(7) && c.suit == hand[0].suit)
+    }
+
+    fn pays(&self) -> usize {
+        3
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+enum BonusKind {
+    SuitedBlackjack,
+    SuitedSevens,
+}
+
+impl BonusKind {
+    fn bonus(&self) -> Box<dyn HandBonus> {
+        match self {
+            BonusKind::SuitedBlackjack => Box::new(SuitedBlackjack),
+            BonusKind::SuitedSevens => Box::new(SuitedSevens),
         }
-        self.evaluate_game_outcomes();
     }
+}
 
-    fn calculate_hand_value(hand: &[Card]) -> usize {
-        let mut value = 0;
-        let mut aces = 0;
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+enum HoleCard {
+    American, // Dealer takes both cards up front
+    EuropeanNHC, // No hole card: the dealer's second card comes after the player acts
+}
 
-        for card in hand {
-            match card.value {
-                Value::Ace => aces += 1,
-                _ => value += card.value() as usize,
-            }
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct ValueRules {
+    aces_low_only: bool, // Hard mode: aces never count as 11
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+struct BeginnerGuard {
+    hit_under: usize, // Standing is disabled below this total
+    stand_at: usize,  // Hitting is disabled at or above this total
+}
+
+impl Default for BeginnerGuard {
+    fn default() -> Self {
+        BeginnerGuard {
+            hit_under: 12,
+            stand_at: 17,
         }
+    }
+}
 
-        for _ in 0..aces {
-            if value + 11 > 21 {
-                value += 1; // Use Ace as 1
-            } else {
-                value += 11; // Use Ace as 11, potentially
-            }
+trait Storage {
+    fn read(&self, key: &str) -> Option<String>; // None if nothing is stored or storage is unavailable
+    fn write(&self, key: &str, value: &str) -> io::Result<()>;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct FileStorage;
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Storage for FileStorage {
+    fn read(&self, key: &str) -> Option<String> {
+        std::fs::read_to_string(format!("{}.json", key)).ok()
+    }
+
+    fn write(&self, key: &str, value: &str) -> io::Result<()> {
+        std::fs::write(format!("{}.json", key), value)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+struct LocalStorage;
+
+#[cfg(target_arch = "wasm32")]
+impl LocalStorage {
+    fn storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()? // Browsers can disable it, e.g. in private mode
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Storage for LocalStorage {
+    fn read(&self, key: &str) -> Option<String> {
+        Self::storage()?.get_item(key).ok()?
+    }
+
+    fn write(&self, key: &str, value: &str) -> io::Result<()> {
+        let storage = Self::storage().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "localStorage is unavailable"))?;
+        storage
+            .set_item(key, value)
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "localStorage write failed"))
+    }
+}
+
+fn platform_storage() -> Box<dyn Storage> {
+    #[cfg(target_arch = "wasm32")]
+    let storage = LocalStorage;
+    #[cfg(not(target_arch = "wasm32"))]
+    let storage = FileStorage;
+    Box::new(storage)
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)] // Settings files from older versions fill in new rules with defaults
+struct RuleSet {
+    low_deck_policy: LowDeckPolicy,
+    beginner_guard: Option<BeginnerGuard>,
+    max_splits: usize,
+    max_ace_splits: usize, // Aces are also held to max_splits
+    dealer_play: DealerPlay,
+    dealer_threshold: DealerThreshold, // Only consulted by standard dealer play
+    value_rules: ValueRules,
+    shuffle_kind: ShuffleKind,
+    double_restriction: DoubleRule,
+    burn_cards: usize, // Discarded from the top after every shuffle
+    dealer_peek: bool, // US style: a dealer natural is revealed before the player acts
+    double_after_split: bool,
+    split_aces_locked: bool, // Split aces get one card each and no further play
+    hole_card: HoleCard,
+    blind_first_card: bool, // Novelty: the player's first card stays hidden until they stand
+    charlie: Option<usize>, // Cards without busting that win automatically
+    bonuses: Vec<BonusKind>, // House promotions paid on winning hands
+    forgiving_first_hit: bool, // Casual play for kids: a hand's first hit never busts
+    double_for_less: bool, // The double may be any amount up to the hand's bet
+    allow_split_tens: bool, // Some tables refuse to split any ten-value pair
+    dealer_22_pushes: bool, // Free Bet style: a dealer 22 pushes instead of busting
+    chip_denomination: usize, // Smallest chip; stakes and payouts are whole numbers of these
+    num_decks: usize,
+    penetration: f32, // Fraction of the shoe dealt before the cut card comes out
+    csm: bool, // Continuous shuffler: dealt cards go straight back, so every round comes from a full shoe
+    max_rounds_per_shoe: Option<usize>, // Reshuffle after this many rounds even if the cut card hasn't come out
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        RuleSet {
+            low_deck_policy: LowDeckPolicy::Reshuffle,
+            beginner_guard: None, // Guided play is opt-in
+            max_splits: 3, // Up to four hands
+            max_ace_splits: 1,
+            dealer_play: DealerPlay::Standard,
+            dealer_threshold: DealerThreshold::default(),
+            value_rules: ValueRules::default(),
+            shuffle_kind: ShuffleKind::Perfect,
+            double_restriction: DoubleRule::Any,
+            burn_cards: 0,
+            dealer_peek: false,
+            double_after_split: true,
+            split_aces_locked: false,
+            hole_card: HoleCard::American,
+            blind_first_card: false,
+            charlie: None,
+            bonuses: Vec::new(),
+            forgiving_first_hit: false,
+            double_for_less: false,
+            allow_split_tens: true,
+            dealer_22_pushes: false,
+            chip_denomination: 1,
+            num_decks: 6,
+            penetration: 0.75,
+            csm: false,
+            max_rounds_per_shoe: None,
         }
+    }
+}
 
-        value
+impl RuleSet {
+    const SETTINGS_KEY: &'static str = "blackjack_rules";
+
+    fn load() -> Self {
+        match platform_storage().read(Self::SETTINGS_KEY) {
+            Some(json) => serde_json::from_str(&json).unwrap_or_else(|err| {
+                eprintln!("Invalid settings file, using default rules: {}", err);
+                RuleSet::default()
+            }),
+            None => RuleSet::default(), // No settings saved yet
+        }
     }
 
-    fn evaluate_game_outcomes(&mut self) {
-        let dealer_value = Self::calculate_hand_value(&self.dealer_hand);
-        let dealer_bust = dealer_value > 21;
-        let mut message = String::from("Round Over: ");
+    fn save(&self) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        platform_storage().write(Self::SETTINGS_KEY, &json)
+    }
 
-        for (index, hand) in self.player_hands.iter().enumerate() {
-            let hand_value = Self::calculate_hand_value(hand);
-            if hand_value > 21 {
-                message.push_str(&format!("Hand {} Busted. ", index + 1));
-            } else if hand_value > dealer_value || dealer_bust {
-                message.push_str(&format!("Hand {} Won! ", index + 1));
-                self.total_money += self.player_bets[index] * 2; // Win double the bet
-            } else if hand_value < dealer_value {
-                message.push_str(&format!("Hand {} Lost. ", index + 1));
-            } else {
-                message.push_str(&format!("Hand {} Push. ", index + 1));
-                self.total_money += self.player_bets[index]; // Return the bet
-            }
+    fn describe(&self) -> Vec<String> {
+        let mut lines = vec![
+            match (self.num_decks, self.csm) {
+                (1, true) => String::from("1 deck in a continuous shuffler"),
+                (n, true) => format!("{} decks in a continuous shuffler", n),
+                (1, false) => format!("1 deck, reshuffled after {:.0}% is dealt", self.penetration * 100.0),
+                (n, false) => format!("{} decks, reshuffled after {:.0}% is dealt", n, self.penetration * 100.0),
+            },
+            match self.dealer_play {
+                DealerPlay::Standard if self.dealer_threshold == DealerThreshold::default() => String::from("Dealer stands on all 17s"),
+                DealerPlay::Standard => format!(
+                    "Dealer stands on hard {} and soft {}",
+                    self.dealer_threshold.stand_hard, self.dealer_threshold.stand_soft
+                ),
+                DealerPlay::BeatBestHand => String::from("Dealer draws until beating your best hand"),
+            },
+            String::from("Blackjack pays 3:2, rounded up to a whole chip"),
+            String::from(match self.double_restriction {
+                DoubleRule::Any => "Double on any two cards",
+                DoubleRule::NineTenEleven => "Double on hard 9, 10 or 11 only",
+                DoubleRule::TenEleven => "Double on hard 10 or 11 only",
+            }),
+            String::from(if self.double_after_split { "Double after split allowed" } else { "No double after split" }),
+            String::from(if self.double_for_less { "Double for less allowed" } else { "Doubles match the original bet" }),
+            match self.max_splits {
+                0 => String::from("No splitting"),
+                1 => String::from("Split once"),
+                n => format!("Split up to {} times", n),
+            },
+            String::from(if self.allow_split_tens { "Tens may be split" } else { "Tens cannot be split" }),
+            match self.max_ace_splits.min(self.max_splits) {
+                0 => String::from("Aces cannot be split"),
+                1 => String::from("Aces split once"),
+                n => format!("Aces split up to {} times", n),
+            },
+            String::from(match self.low_deck_policy {
+                LowDeckPolicy::Reshuffle => "Discards are reshuffled if the shoe runs out",
+                LowDeckPolicy::VoidRound => "Round is void when the deck runs out",
+            }),
+            match self.shuffle_kind {
+                ShuffleKind::Perfect => String::from("Perfect shuffle"),
+                ShuffleKind::Riffle { passes } => format!("Riffle shuffle, {} passes", passes),
+                ShuffleKind::Overhand { passes } => format!("Overhand shuffle, {} passes", passes),
+            },
+        ];
+        for kind in &self.bonuses {
+            let bonus = kind.bonus();
+            lines.push(format!("{} pays {}:1", bonus.name(), bonus.pays()));
+        }
+        if self.forgiving_first_hit {
+            lines.push(String::from("Forgiving: the first hit on a hand never busts"));
+        }
+        if self.dealer_22_pushes {
+            lines.push(String::from("Dealer 22 pushes every hand except a blackjack"));
+        }
+        if let Some(cards) = self.charlie {
+            lines.push(format!("{}-card Charlie wins", cards));
         }
+        if let Some(rounds) = self.max_rounds_per_shoe {
+            lines.push(format!("Reshuffled after {} rounds at most", rounds));
+        }
+        if self.blind_first_card {
+            lines.push(String::from("Blind: your first card is hidden until you stand"));
+        }
+        if self.hole_card == HoleCard::EuropeanNHC {
+            lines.push(String::from("No hole card: doubles and splits lose in full to a dealer blackjack"));
+        }
+        if self.split_aces_locked {
+            lines.push(String::from("Split aces receive one card each"));
+        }
+        if self.dealer_peek {
+            lines.push(String::from("Dealer peeks for blackjack"));
+        }
+        if self.chip_denomination > 1 {
+            lines.push(format!("Bets in multiples of {}", self.chip_denomination));
+        }
+        if self.burn_cards > 0 {
+            lines.push(format!("Burn {} after each shuffle", self.burn_cards));
+        }
+        if self.value_rules.aces_low_only {
+            lines.push(String::from("Aces only count as 1"));
+        }
+        if let Some(guard) = self.beginner_guard {
+            lines.push(format!("Beginner guard: always hit under {}, always stand at {}", guard.hit_under, guard.stand_at));
+        }
+        lines
+    }
 
-        self.game_state = GameState::GameOver(message);
+    fn snap_to_chips(&self, amount: usize) -> usize {
+        // Nearest whole number of chips, halves rounding up, and never less than one chip
+        let chip = self.chip_denomination.max(1);
+        ((amount + chip / 2) / chip * chip).max(chip)
+    }
+
+    fn natural_winnings(&self, bet: usize) -> usize {
+        // 3:2, rounded up to a whole chip when it doesn't come out even: a $15 bet wins $23 with $1 chips,
+        // and a single $5 chip wins $10 rather than falling back to even money
+        let chip = self.chip_denomination.max(1);
+        bet.saturating_mul(3).div_ceil(chip.saturating_mul(2)).saturating_mul(chip)
+    }
+
+    fn estimated_edge(&self) -> f64 {
+        // House edge in percent from published per-rule adjustments, starting from a single-deck game
+        // with 3:2 blackjack, stand on soft 17, doubling on any two cards and no double after split.
+        // Assumes basic strategy and the standard dealer; novelty rules aren't covered.
+        let mut edge = match self.num_decks {
+            1 => 0.0,
+            2 => 0.32,
+            3..=4 => 0.48,
+            5..=6 => 0.54,
+            _ => 0.57,
+        };
+        if self.dealer_threshold.stand_soft > 17 {
+            edge += 0.22; // Dealer hits soft 17
+        }
+        edge += match self.double_restriction {
+            DoubleRule::Any => 0.0,
+            DoubleRule::NineTenEleven => 0.12,
+            DoubleRule::TenEleven => 0.26,
+        };
+        if self.double_after_split {
+            edge -= 0.14;
+        }
+        if self.max_ace_splits > 1 && self.max_splits > 1 {
+            edge -= 0.03; // Resplitting aces
+        }
+        if !self.split_aces_locked {
+            edge -= 0.19; // Drawing to split aces
+        }
+        if self.hole_card == HoleCard::EuropeanNHC || !self.dealer_peek {
+            edge += 0.11; // Doubles and splits lose in full to a dealer blackjack
+        }
+        edge -= match self.charlie {
+            Some(cards) if cards <= 5 => 1.46,
+            Some(6) => 0.16,
+            Some(_) => 0.01,
+            None => 0.0,
+        };
+        edge
     }
 }
 
-impl epi::App for BlackjackApp {
-    fn name(&self) -> &str {
-        "Blackjack"
+struct BlackjackApp {
+    deck: Vec<Card>,
+    player_hands: Vec<Vec<Card>>,
+    dealer_hand: Vec<Card>,
+    current_hand: usize,
+    game_state: GameState,
+    player_bets: Vec<usize>,
+    split_hands: Vec<bool>, // Whether each hand came from a split
+    total_money: usize, // Excludes whatever is staked on the table
+    wager: usize, // Opening bet for the next deal, chosen while betting
+    bet_text: String, // Typed bet, which may not parse yet
+    money_format: MoneyFormat,
+    seed: u64, // Kept so the session can be exported and replayed
+    shuffles: u64, // Shuffles since seeding; with the seed this is the RNG position
+    lock_seed: bool, // New Game replays the same shoe, for comparing rules
+    rules: RuleSet,
+    shoe_results: Vec<i64>, // Net of the rounds dealt from each shoe, the current one last
+    outcomes: Vec<Outcome>, // Result of each hand once the round is settled
+    last_error: Option<GameError>, // Shown in the UI until the next action
+    displayed_money: f32, // Eases toward total_money for the bankroll readout
+    animate_money: bool,
+    round_actions: Vec<(usize, Action)>,
+    round_history: Vec<RoundRecord>,
+    reveal_hole_card: bool, // Teaching aid: show the dealer's second card during play
+    forced_upcard: Option<Value>, // Practice scenarios against a fixed dealer upcard
+    flash: Option<(FlashKind, Instant)>, // Brief overlay after a bust or natural
+    flash_effects: bool,
+    shoe_size: usize, // Cards in the shoe when it was last built
+    burned: Vec<Card>, // Burned since the last shuffle, never dealt
+    discards: Vec<Card>, // Cleared from the table since the last shuffle
+    needs_reshuffle: bool, // The cut card is out; the next deal starts a new shoe
+    shoe_rounds: usize, // Rounds dealt from the current shoe
+    settled_round_saved: bool,
+    custom_bonuses: Vec<Box<dyn HandBonus>>, // Registered in code, on top of the rule set's bonuses
+    settlement_hook: Option<SettlementHook>, // Registered in code for novelty house rules
+    bonus_winnings: Vec<usize>, // Extra paid on each hand this round beyond 1:1
+    deal_log: Vec<(DealTarget, Card)>, // Every card dealt this round, in order
+    show_deal_log: bool, // Debug overlay
+    session_paste: String, // Exported session JSON waiting to be loaded
+    training_mode: bool, // Counting practice readouts
+    quiz: QuizState, // Hand-total drill, apart from the table
+    fan_cards: bool, // Overlapping card faces, otherwise a plain text list
+    card_theme: CardTheme,
+    shoe_count_display: ShoeCountDisplay,
+    confirm_abandon: bool, // Give Up was clicked and awaits confirmation
+    round_abandoned: bool,
+    auto_topup: Option<usize>, // Practice bankroll restored whenever not even one chip is left
+    dealer_draws: Vec<(Card, usize)>, // Cards the dealer drew on its turn, with the total after each
+    chip_animation: Option<Instant>, // When settled chips started sliding; money has already moved
+    recent_bets: Vec<usize>, // Oldest first, offered as presets when betting
+    show_player_total: bool,
+    events: Vec<GameEvent>, // Everything applied since the game started from its seed
+    logged_rules: RuleSet, // As of the last Rules event, or the start of the log
+    low_shoe_warning_threshold: usize, // Cards before the cut card at which counters are warned of the coming reshuffle
+    narrate_dealer: bool, // Spell out each dealer decision at the reveal
+    dealer_narration: Vec<String>, // This round's decisions, recorded whether or not they're shown
+    auto_stand_21: bool, // Otherwise a hand at 21 waits for an explicit Stand
+    auto_split_aces_eights: bool, // Beginner assist, whenever the split is affordable
+    assist_note: Option<String>, // Why the assist played for the player this round
+    animate_deal: bool,
+    win_condition: WinCondition,
+    goal_result: Option<bool>, // Set once the win condition succeeds or fails
+    rounds_played: usize, // Since the last new game, unlike the capped history
+    session_start: Instant, // Restarted with each new game, like rounds_played
+    best_net: i64, // Session extremes, kept apart from the capped history
+    worst_net: i64,
+    opening_count: f64, // True count the current round was dealt at
+    pit_boss: bool, // Warn when bets follow the count too closely
+    deal_animation: Option<(usize, Instant)>, // Opening cards shown so far, and when the last one appeared
+    first_card_hidden: bool, // Blind variant, until the first hand is finished
+    advance_delay: f32, // Seconds to pause on a finished split hand
+    pending_advance: Option<Instant>,
+    double_amount: usize, // Chosen extra stake when doubling for less
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+enum Action {
+    Hit,
+    Stand,
+    DoubleDown,
+    Split,
+}
+
+impl Action {
+    fn label(&self) -> &'static str {
+        match self {
+            Action::Hit => "Hit",
+            Action::Stand => "Stand",
+            Action::DoubleDown => "Double Down",
+            Action::Split => "Split",
+        }
     }
+}
 
-    fn update(&mut self, ctx: &egui::CtxRef, _frame: &mut epi::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("Blackjack");
-            match self.game_state {
-                GameState::Betting => {
-                    if ui.button("Place Bet and Start").clicked() {
-                        self.new_round();
-                    }
-                },
-                GameState::PlayerTurn => {
-                    ui.label(format!("Current Hand: {}", self.player_hands[self.current_hand].iter().map(|c| c.to_string()).collect::<Vec<_>>().join(", ")));
-                    if ui.button("Hit").clicked() {
-                        self.hit();
-                    }
-                    if ui.button("Stand").clicked() {
-                        self.stand();
-                    }
-                    if ui.button("Double Down").clicked() {
-                        self.double_down();
-                    }
-                    if self.player_hands[self.current_hand].len() == 2 && Self::can_split(&self.player_hands[self.current_hand]) {
-                        if ui.button("Split").clicked() {
-                            self.split();
-                        }
-                    }
-                },
-                GameState::DealerTurn => {
-                    ui.label("Dealer's turn...");
-                },
-                GameState::GameOver(ref message) => {
-                    ui.label(message);
-                    if ui.button("Play Again").clicked() {
-                        self.new_round();
-                    }
-                },
-            }
-        });
+#[derive(Debug, PartialEq)]
+enum GameError {
+    InsufficientFunds,
+    IllegalAction,
+    DeckEmpty,
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameError::InsufficientFunds => write!(f, "Insufficient funds."),
+            GameError::IllegalAction => write!(f, "That action isn't allowed right now."),
+            GameError::DeckEmpty => write!(f, "Deck depleted. Round voided."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+enum Outcome {
+    Won,
+    Blackjack,
+    Lost,
+    Push,
+    BlackjackPush, // Both sides had a natural
+    Busted,
+}
+
+impl Outcome {
+    fn label(&self) -> &'static str {
+        match self {
+            Outcome::Won => "Won!",
+            Outcome::Blackjack => "Blackjack!",
+            Outcome::Lost => "Lost.",
+            Outcome::Push => "Push.",
+            Outcome::BlackjackPush => "Both blackjack - push.",
+            Outcome::Busted => "Busted.",
+        }
+    }
+
+    fn color(&self) -> egui::Color32 {
+        match self {
+            Outcome::Won | Outcome::Blackjack => egui::Color32::GREEN,
+            Outcome::Push | Outcome::BlackjackPush => egui::Color32::GRAY,
+            Outcome::Lost | Outcome::Busted => egui::Color32::RED,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FlashKind {
+    Bust,
+    Blackjack,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum WinCondition {
+    Endless,
+    TargetBankroll(usize), // Win on reaching this bankroll
+    SurviveRounds(usize), // Win by playing this many rounds without going broke
+    BankrollFloor(usize), // Lose on dropping below this bankroll
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DealTarget {
+    Player(usize), // Hand index
+    Dealer,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CompositionStats {
+    high: usize, // Tens and aces
+    neutral: usize, // 7 to 9
+    low: usize, // 2 to 6
+}
+
+impl CompositionStats {
+    fn share(&self, count: usize) -> f32 {
+        let total = self.high + self.neutral + self.low;
+        if total == 0 {
+            0.0
+        } else {
+            count as f32 / total as f32
+        }
+    }
+}
+
+#[derive(Default)]
+struct QuizState {
+    hand: Vec<Card>,
+    answer: String, // Typed total, graded on Check
+    asked: usize,
+    correct: usize,
+    last_result: Option<(bool, usize)>, // Whether the last answer was right, and the real total
+}
+
+impl QuizState {
+    fn next_hand(&mut self, rng: &mut impl Rng) {
+        // Drawn from a deck of its own, so drilling never disturbs the seeded shoe
+        let mut deck = BlackjackApp::create_deck(1);
+        deck.shuffle(rng);
+        let size = rng.gen_range(2..=4);
+        self.hand = deck.into_iter().take(size).collect();
+        self.answer.clear();
+    }
+
+    fn grade(&mut self, value_rules: ValueRules) -> Option<bool> {
+        let guess = self.answer.trim().parse::<usize>().ok()?; // Not a number yet, so not an attempt
+        let total = BlackjackApp::calculate_hand_value(&self.hand, value_rules);
+        let right = guess == total;
+        self.asked += 1;
+        if right {
+            self.correct += 1;
+        }
+        self.last_result = Some((right, total));
+        Some(right)
+    }
+
+    fn accuracy(&self) -> f32 {
+        if self.asked == 0 {
+            0.0
+        } else {
+            self.correct as f32 / self.asked as f32
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct RoundRecord {
+    bets: Vec<usize>,
+    player_hands: Vec<Vec<Card>>,
+    dealer_hand: Vec<Card>,
+    outcomes: Vec<Outcome>,
+    actions: Vec<(usize, Action)>, // Hand index and the action taken on it
+    net: i64,
+    bankroll: usize, // After settlement
+    bad_beat: bool, // A strong hand lost to a dealer who drew out
+    #[serde(default)]
+    true_count: f64, // When the opening bet went out
+    #[serde(default)]
+    events: Vec<GameEvent>, // Logged since the previous round settled, so replaying them in order rebuilds the session
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum GameEvent {
+    Bet(usize), // Opening stake; applying it deals a new round
+    Hit,
+    Stand,
+    Split,
+    Double(usize), // Extra stake put up
+    GiveUp,
+    Reshuffle,
+    Rules(RuleSet), // Changed mid-session, in effect from here on
+    ForceUpcard(Option<Value>),
+    DealerDraw(Card), // Follows from the player's last action, applying it changes nothing
+    Settle,           // Likewise
+}
+
+#[derive(Serialize, Deserialize)]
+struct GameSnapshot {
+    deck: Vec<Card>,
+    burned: Vec<Card>,
+    #[serde(default)]
+    discards: Option<Vec<Card>>, // Missing from older saves, which dealt every round from a new deck
+    #[serde(default)]
+    needs_reshuffle: bool,
+    #[serde(default)]
+    shoe_rounds: usize,
+    shoe_size: usize,
+    player_hands: Vec<Vec<Card>>,
+    dealer_hand: Vec<Card>,
+    current_hand: usize,
+    player_bets: Vec<usize>,
+    split_hands: Vec<bool>,
+    total_money: usize,
+    game_state: GameState,
+    outcomes: Vec<Outcome>,
+    round_actions: Vec<(usize, Action)>,
+    seed: u64,
+    shuffles: u64,
+    advancing: bool, // Paused on a finished split hand
+    #[serde(default)]
+    events: Vec<GameEvent>,
+    #[serde(default)]
+    wager: usize, // Zero in older saves, which fall back to the opening bet
+    #[serde(default)]
+    first_card_hidden: bool,
+    #[serde(default)]
+    bonus_winnings: Vec<usize>,
+    #[serde(default)]
+    dealer_draws: Vec<(Card, usize)>,
+    #[serde(default)]
+    round_history: Vec<RoundRecord>, // Session stats; older saves start them over
+    #[serde(default)]
+    rounds_played: usize,
+    #[serde(default)]
+    best_net: i64,
+    #[serde(default)]
+    worst_net: i64,
+    #[serde(default)]
+    shoe_results: Vec<i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionExport {
+    seed: u64,
+    rules: RuleSet,
+    rounds: Vec<RoundRecord>, // Replaying their actions from the seed reproduces the same rounds
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+enum GameState {
+    Startup, // Offering to continue a saved game
+    Betting,
+    PlayerTurn,
+    DealerTurn,
+    GameOver(String),
+}
+
+impl Default for BlackjackApp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlackjackApp {
+    const STATE_KEY: &'static str = "blackjack_state";
+
+    fn new() -> Self {
+        Self::starting_from(&*platform_storage())
+    }
+
+    fn starting_from(storage: &dyn Storage) -> Self {
+        // Nothing is dealt until the first bet goes down
+        let mut app = Self::with_rules(RuleSet::load());
+        if Self::has_saved_session(storage) {
+            app.game_state = GameState::Startup;
+        }
+        app
+    }
+
+    fn has_saved_session(storage: &dyn Storage) -> bool {
+        storage.read(Self::STATE_KEY).is_some()
+    }
+
+    fn continue_session(&mut self, storage: &dyn Storage) {
+        if !self.load_state(storage) {
+            self.new_game(); // Nothing usable was saved after all
+        }
+    }
+
+    fn save_state(&self, storage: &dyn Storage) -> io::Result<()> {
+        let json = serde_json::to_string(&self.snapshot())?;
+        storage.write(Self::STATE_KEY, &json)
+    }
+
+    fn load_state(&mut self, storage: &dyn Storage) -> bool {
+        let mut restored = false;
+        if let Some(json) = storage.read(Self::STATE_KEY) {
+            match serde_json::from_str(&json) {
+                Ok(snapshot) => {
+                    self.restore(snapshot);
+                    restored = true;
+                }
+                Err(err) => eprintln!("Invalid saved game, starting fresh: {}", err),
+            }
+        }
+        self.displayed_money = self.total_money as f32;
+        restored
+    }
+
+    fn with_rules(rules: RuleSet) -> Self {
+        Self::undealt(thread_rng().gen(), rules)
+    }
+
+    #[cfg(test)]
+    fn with_seed(seed: u64) -> Self {
+        Self::with_seed_and_rules(seed, RuleSet::default())
+    }
+
+    #[cfg(test)]
+    fn with_seed_and_rules(seed: u64, rules: RuleSet) -> Self {
+        let mut app = Self::undealt(seed, rules);
+        app.new_round();
+        app
+    }
+
+    #[cfg(test)]
+    fn with_stacked_deck(cards: Vec<Card>, rules: RuleSet) -> Self {
+        // Scripted scenarios: the first round is dealt from these cards, in this order, unshuffled
+        let mut app = Self::undealt(0, rules);
+        app.shoe_size = cards.len();
+        app.deck = cards.into_iter().rev().collect(); // Cards come off the end of the deck
+        app.new_round();
+        app.needs_reshuffle = true; // Later rounds come from a real shoe
+        app
+    }
+
+    fn undealt(seed: u64, rules: RuleSet) -> Self {
+        BlackjackApp {
+            deck: Vec::new(),
+            player_hands: vec![Vec::new()],
+            dealer_hand: Vec::new(),
+            current_hand: 0,
+            game_state: GameState::Betting,
+            player_bets: vec![10],
+            split_hands: vec![false],
+            total_money: 100, // Starting money
+            wager: 10,
+            bet_text: String::from("10"),
+            money_format: MoneyFormat::default(),
+            seed, // Seeded so shuffles can be reproduced
+            shuffles: 0,
+            lock_seed: false,
+            logged_rules: rules.clone(),
+            rules,
+            shoe_results: Vec::new(),
+            outcomes: Vec::new(),
+            last_error: None,
+            displayed_money: 100.0,
+            animate_money: true,
+            round_actions: Vec::new(),
+            round_history: Vec::new(),
+            best_net: 0,
+            worst_net: 0,
+            opening_count: 0.0,
+            pit_boss: false,
+            reveal_hole_card: false,
+            forced_upcard: None,
+            flash: None,
+            flash_effects: true,
+            shoe_size: 0,
+            burned: Vec::new(),
+            discards: Vec::new(),
+            needs_reshuffle: false,
+            shoe_rounds: 0,
+            settled_round_saved: false,
+            custom_bonuses: Vec::new(),
+            settlement_hook: None,
+            bonus_winnings: Vec::new(),
+            deal_log: Vec::new(),
+            show_deal_log: false,
+            session_paste: String::new(),
+            training_mode: false,
+            quiz: QuizState::default(),
+            fan_cards: true,
+            card_theme: CardTheme::Classic,
+            shoe_count_display: ShoeCountDisplay::Always,
+            confirm_abandon: false,
+            round_abandoned: false,
+            auto_topup: None,
+            dealer_draws: Vec::new(),
+            chip_animation: None,
+            recent_bets: Vec::new(),
+            show_player_total: true,
+            events: Vec::new(),
+            low_shoe_warning_threshold: 10,
+            narrate_dealer: false,
+            dealer_narration: Vec::new(),
+            auto_stand_21: false,
+            auto_split_aces_eights: false,
+            assist_note: None,
+            animate_deal: true,
+            win_condition: WinCondition::Endless,
+            goal_result: None,
+            rounds_played: 0,
+            session_start: Instant::now(),
+            deal_animation: None,
+            first_card_hidden: false,
+            advance_delay: 0.6,
+            double_amount: 10,
+            pending_advance: None,
+        }
+    }
+
+    fn new_game(&mut self) {
+        if !self.lock_seed {
+            self.seed = thread_rng().gen();
+        }
+        self.shuffles = 0;
+        self.total_money = 100;
+        self.displayed_money = 100.0;
+        self.wager = 10;
+        self.round_history.clear();
+        self.shoe_results.clear();
+        self.best_net = 0;
+        self.worst_net = 0;
+        self.rounds_played = 0;
+        self.session_start = Instant::now();
+        self.goal_result = None;
+        self.events.clear(); // A new log for the new seed
+        self.logged_rules = self.rules.clone();
+        self.deck.clear(); // The first bet deals from a shoe built from the new seed
+        self.burned.clear();
+        self.discards.clear();
+        self.shoe_size = 0;
+        self.game_state = GameState::Betting;
+    }
+
+    fn check_win_condition(&self) -> Option<bool> {
+        let broke = self.total_money < self.rules.chip_denomination.max(1); // Not even the smallest bet is left
+        match self.win_condition {
+            WinCondition::Endless => None,
+            WinCondition::TargetBankroll(target) if self.total_money >= target => Some(true),
+            WinCondition::SurviveRounds(rounds) if self.rounds_played >= rounds => Some(true),
+            WinCondition::BankrollFloor(floor) if self.total_money < floor => Some(false),
+            _ if broke => Some(false),
+            _ => None,
+        }
+    }
+
+    fn create_deck(decks: usize) -> Vec<Card> {
+        let suits = [Suit::Hearts, Suit::Diamonds, Suit::Clubs, Suit::Spades];
+        let values = [
+            Value::Number(2), Value::Number(3), Value::Number(4), Value::Number(5), Value::Number(6),
+            Value::Number(7), Value::Number(8), Value::Number(9), Value::Number(10),
+            Value::Jack, Value::Queen, Value::King, Value::Ace,
+        ];
+        let mut deck = Vec::new();
+
+        for _ in 0..decks.max(1) {
+            for &suit in suits.iter() {
+                for &value in values.iter() {
+                    deck.push(Card { value, suit });
+                }
+            }
+        }
+
+        deck
+    }
+
+    fn can_split(hand: &[Card]) -> bool {
+        hand.len() == 2 && hand[0].value == hand[1].value
+    }
+
+    fn within_split_caps(&self) -> bool {
+        let splitting_aces = self.player_hands[self.current_hand][0].value == Value::Ace;
+        self.player_hands.len() <= self.rules.max_splits // One hand per split beyond the first
+            && (!splitting_aces || self.ace_splits() < self.rules.max_ace_splits)
+    }
+
+    fn split_rank_allowed(&self) -> bool {
+        self.rules.allow_split_tens || self.player_hands[self.current_hand][0].value() != 10
+    }
+
+    fn ace_splits(&self) -> usize {
+        self.round_actions
+            .iter()
+            .filter(|&&(index, action)| action == Action::Split && self.player_hands[index][0].value == Value::Ace)
+            .count()
+    }
+
+    fn can_split_now(&self) -> bool {
+        !self.is_card_hidden(self.current_hand, 0)
+            && Self::can_split(&self.player_hands[self.current_hand])
+            && self.split_rank_allowed()
+            && self.within_split_caps()
+            && self.total_money >= self.player_bets[self.current_hand]
+    }
+
+    fn shuffle_deck(&mut self) {
+        // Each shuffle gets its own stream, so a snapshot only needs the shuffle count to resume
+        let mut rng = StdRng::seed_from_u64(self.seed.wrapping_add(self.shuffles));
+        self.shuffles += 1;
+        self.rules.shuffle_kind.apply(&mut self.deck, &mut rng);
+    }
+
+    fn fresh_deck(&mut self) {
+        self.deck = Self::create_deck(self.rules.num_decks);
+        self.shoe_size = self.deck.len();
+        // Cards still on the table from the last round belong to the new shoe and are discarded with the next deal
+        for card in self.player_hands.iter().flatten().chain(&self.dealer_hand) {
+            if let Some(position) = self.deck.iter().position(|c| c.suit == card.suit && c.value == card.value) {
+                self.deck.remove(position);
+            }
+        }
+        self.discards.clear();
+        self.needs_reshuffle = false;
+        self.shoe_rounds = 0;
+        if !self.rules.csm || self.shoe_results.is_empty() {
+            self.shoe_results.push(0); // A continuous shuffler never finishes a shoe
+        }
+        self.shuffle_deck();
+        let burn = self.rules.burn_cards.min(self.deck.len().saturating_sub(self.cards_needed_for_deal()));
+        self.burned = self.deck.split_off(self.deck.len() - burn);
+    }
+
+    fn reshuffle_discards(&mut self) {
+        // Mid-round, the discards and burned cards go back into the shoe; the table keeps its cards
+        self.deck.append(&mut self.discards);
+        self.deck.append(&mut self.burned);
+        self.shuffle_deck();
+    }
+
+    fn cards_remaining(&self) -> usize {
+        self.deck.len()
+    }
+
+    fn cards_to_cut(&self) -> usize {
+        // The cut card sits where the penetration runs out, counted from the back of the shoe
+        let cut = (self.shoe_size as f32 * (1.0 - self.rules.penetration)) as usize;
+        self.cards_remaining().saturating_sub(cut)
+    }
+
+    fn cards_needed_for_deal(&self) -> usize {
+        4 // Two for the player's box and two for the dealer
+    }
+
+    fn maybe_reshuffle(&mut self) {
+        // Between rounds: the cut card, the round limit, or a shoe too short for the deal
+        let round_limit = matches!(self.rules.max_rounds_per_shoe, Some(rounds) if self.shoe_rounds >= rounds);
+        if self.rules.csm || self.needs_reshuffle || round_limit || self.cards_remaining() < self.cards_needed_for_deal() {
+            self.fresh_deck();
+        }
+    }
+
+    fn reshuffle_now(&mut self) {
+        self.record(GameEvent::Reshuffle);
+        self.fresh_deck(); // Bankroll and round history are untouched, unlike new_game
+    }
+
+    fn remaining_composition(&self) -> CompositionStats {
+        // Everything still in the shoe; dealt and burned cards are out of it
+        let mut stats = CompositionStats { high: 0, neutral: 0, low: 0 };
+        for card in &self.deck {
+            match card.value {
+                Value::Ace | Value::Number(10) | Value::Jack | Value::Queen | Value::King => stats.high += 1,
+                Value::Number(n) if n >= 7 => stats.neutral += 1,
+                Value::Number(_) => stats.low += 1,
+            }
+        }
+        stats
+    }
+
+    fn true_count(&self) -> f64 {
+        // Hi-Lo from the cards the player hasn't seen; a full shoe balances to zero
+        let unseen = self.deck.iter().chain(self.burned.iter());
+        let running: i64 = unseen
+            .map(|card| match card.value {
+                Value::Ace | Value::Number(10) | Value::Jack | Value::Queen | Value::King => 1,
+                Value::Number(n) if n >= 7 => 0,
+                Value::Number(_) => -1,
+            })
+            .sum();
+        let decks_left = (self.cards_remaining() + self.burned.len()) as f64 / 52.0;
+        if decks_left == 0.0 {
+            0.0
+        } else {
+            running as f64 / decks_left
+        }
+    }
+
+    fn count_edge(&self) -> f64 {
+        // Player edge as a fraction of the bet: about half a percent per true count, less the house edge
+        (self.true_count() * 0.5 - self.rules.estimated_edge()) / 100.0
+    }
+
+    fn kelly_bet(bankroll: usize, edge_estimate: f64, table_min: usize, table_max: usize) -> usize {
+        // Simplified Kelly: bet the edge over blackjack's variance of about 1.3, as a share of the bankroll
+        const VARIANCE: f64 = 1.3;
+        let table_min = table_min.max(1);
+        if edge_estimate <= 0.0 {
+            return table_min; // No edge, so the least the table allows
+        }
+        let stake = (bankroll as f64 * edge_estimate / VARIANCE) as usize / table_min * table_min; // Whole chips
+        stake.clamp(table_min, table_max.max(table_min))
+    }
+
+    fn heat_level(&self) -> f64 {
+        // Correlation of opening bets with the count over recent rounds, ignoring a negative one
+        const WINDOW: usize = 10;
+        let recent = &self.round_history[self.round_history.len().saturating_sub(WINDOW)..];
+        if recent.len() < 3 {
+            return 0.0;
+        }
+        let n = recent.len() as f64;
+        let bets: Vec<f64> = recent.iter().map(|r| r.bets.first().copied().unwrap_or(0) as f64).collect();
+        let counts: Vec<f64> = recent.iter().map(|r| r.true_count).collect();
+        let mean_bet = bets.iter().sum::<f64>() / n;
+        let mean_count = counts.iter().sum::<f64>() / n;
+        let mut covariance = 0.0;
+        let mut bet_spread = 0.0;
+        let mut count_spread = 0.0;
+        for (bet, count) in bets.iter().zip(counts.iter()) {
+            covariance += (bet - mean_bet) * (count - mean_count);
+            bet_spread += (bet - mean_bet).powi(2);
+            count_spread += (count - mean_count).powi(2);
+        }
+        if bet_spread == 0.0 || count_spread == 0.0 {
+            return 0.0; // A flat bettor gives nothing away
+        }
+        (covariance / (bet_spread * count_spread).sqrt()).max(0.0)
+    }
+
+    fn low_shoe_warning(&self) -> bool {
+        // No shoe yet before the first deal
+        self.shoe_size > 0 && (self.needs_reshuffle || self.cards_to_cut() < self.low_shoe_warning_threshold)
+    }
+
+    fn cut_card_mark(&self) -> Option<f32> {
+        // Where the cut card sits along the shoe bar; a continuous shuffler has none
+        if self.rules.csm {
+            None
+        } else {
+            Some(self.rules.penetration)
+        }
+    }
+
+    fn shoe_penetration(&self) -> f32 {
+        if self.shoe_size == 0 {
+            return 0.0;
+        }
+        1.0 - self.cards_remaining() as f32 / self.shoe_size as f32 // Fraction of the shoe already dealt
+    }
+
+    fn new_round(&mut self) {
+        self.record(GameEvent::Bet(self.wager));
+        // The stake leaves the bankroll as the cards come out, and settlement pays back the whole return
+        self.player_bets = vec![self.wager]; // Doubles and splits stay with the round they were made in
+        self.total_money = self.total_money.saturating_sub(self.wager);
+        let table = self.player_hands.drain(..).flatten().chain(self.dealer_hand.drain(..));
+        self.discards.extend(table);
+        self.maybe_reshuffle();
+        self.shoe_rounds += 1;
+        if let Some(value) = self.forced_upcard {
+            if !self.deck.iter().any(|c| c.value == value) {
+                self.reshuffle_discards(); // The shoe is out of that rank, so bring every discard and burn card back in
+            }
+            let upcard_slot = self.deck.len() - 2; // Second card off the top is the dealer's first
+            if let Some(position) = self.deck.iter().position(|c| c.value == value) {
+                self.deck.swap(position, upcard_slot);
+            }
+        }
+        self.opening_count = self.true_count();
+        self.player_hands = vec![Vec::new()];
+        self.dealer_hand = Vec::new();
+        self.deal_log.clear();
+        self.split_hands = vec![false];
+        self.outcomes.clear();
+        self.round_actions.clear();
+        self.pending_advance = None;
+        self.confirm_abandon = false;
+        self.round_abandoned = false;
+        self.dealer_draws.clear();
+        self.dealer_narration.clear();
+        self.chip_animation = None;
+        self.last_error = None;
+        self.assist_note = None;
+        self.current_hand = 0;
+        self.double_amount = self.player_bets[0]; // Offer a full double unless the player slides it down
+        for target in self.opening_deal() {
+            // Drawn like any other card, so the cut card can come out during the deal
+            match self.draw_card() {
+                Some(card) => self.place_card(target, card),
+                None => {
+                    self.void_round(); // maybe_reshuffle leaves enough, so this is only a safeguard
+                    return;
+                }
+            }
+        }
+        self.assert_deal_consistent();
+        self.first_card_hidden = self.rules.blind_first_card;
+        self.deal_animation = if self.animate_deal { Some((0, Instant::now())) } else { None };
+        self.game_state = GameState::PlayerTurn;
+        if self.is_natural(0) && !self.first_card_hidden {
+            self.trigger_flash(FlashKind::Blackjack);
+        }
+        if self.rules.dealer_peek && self.dealer_has_natural() {
+            // Both cards are shown at game over, and a player natural still pushes
+            self.game_state = GameState::DealerTurn;
+            self.evaluate_game_outcomes();
+            return;
+        }
+        if self.is_natural(0) && !self.first_card_hidden {
+            self.finish_hand(); // Nothing to decide on a natural, it's paid as soon as the dealer's hand is known
+            return;
+        }
+        self.auto_split_pairs();
+    }
+
+    fn auto_split_pairs(&mut self) {
+        if !self.auto_split_aces_eights {
+            return;
+        }
+        // A split can deal the same rank again, so keep going until the current hand isn't a pair worth splitting
+        while matches!(self.game_state, GameState::PlayerTurn) {
+            let hand = &self.player_hands[self.current_hand];
+            let note = match hand.first().map(|c| c.value) {
+                Some(Value::Ace) => "Split aces automatically: two hands starting on 11 beat one soft 12.",
+                Some(Value::Number(8)) => "Split eights automatically: 16 is the worst total, and two hands starting on 8 lose less.",
+                _ => break,
+            };
+            if !self.can_split_now() || self.split().is_err() {
+                break; // Unaffordable or against the rules, so the pair is played as it stands
+            }
+            self.assist_note = Some(String::from(note));
+        }
+    }
+
+    fn trigger_flash(&mut self, kind: FlashKind) {
+        if self.flash_effects {
+            self.flash = Some((kind, Instant::now()));
+        }
+    }
+
+    fn force_dealer_upcard(&mut self, value: Option<Value>) {
+        if value != self.forced_upcard {
+            self.record(GameEvent::ForceUpcard(value));
+            self.forced_upcard = value; // Applies from the next deal onwards
+        }
+    }
+
+    fn opening_deal(&self) -> Vec<DealTarget> {
+        // Casino order: a card to the box, the upcard, the box's second card, then the hole card
+        let mut order = vec![DealTarget::Player(0), DealTarget::Dealer, DealTarget::Player(0)];
+        if self.rules.hole_card == HoleCard::American {
+            order.push(DealTarget::Dealer);
+        }
+        order
+    }
+
+    fn check_deal(&self) -> Result<(), String> {
+        let opening = self.opening_deal();
+        let expected = |target| opening.iter().filter(|&&t| t == target).count();
+        if self.player_hands.len() != 1 || self.player_hands[0].len() != expected(DealTarget::Player(0)) {
+            return Err(format!("player was dealt {:?} cards", self.player_hands.iter().map(Vec::len).collect::<Vec<_>>()));
+        }
+        if self.dealer_hand.len() != expected(DealTarget::Dealer) {
+            return Err(format!("dealer was dealt {} cards", self.dealer_hand.len()));
+        }
+
+        let decks = self.shoe_size.div_ceil(52); // As many copies of a card as the shoe was built with
+        let mut seen: Vec<&Card> = Vec::new();
+        let table = self.player_hands.iter().flatten().chain(&self.dealer_hand);
+        let all_cards = table.chain(&self.deck).chain(&self.burned).chain(&self.discards);
+        for card in all_cards {
+            if seen.iter().filter(|c| c.suit == card.suit && c.value == card.value).count() >= decks.max(1) {
+                return Err(format!("{} appears more than {} times", card.short(), decks.max(1)));
+            }
+            seen.push(card);
+        }
+        if seen.len() != self.shoe_size {
+            return Err(format!("{} cards accounted for in a shoe of {}", seen.len(), self.shoe_size));
+        }
+        Ok(())
+    }
+
+    fn assert_deal_consistent(&self) {
+        // Debug builds only: a bad deal here means a bug in dealing, not something to recover from
+        if cfg!(debug_assertions) {
+            if let Err(problem) = self.check_deal() {
+                panic!("inconsistent deal: {}", problem);
+            }
+        }
+    }
+
+    fn visible_cards(&self, target: DealTarget) -> usize {
+        match self.deal_animation {
+            Some((shown, _)) => self.deal_log[..shown.min(self.deal_log.len())].iter().filter(|(t, _)| *t == target).count(),
+            None => usize::MAX,
+        }
+    }
+
+    fn place_card(&mut self, target: DealTarget, card: Card) {
+        self.deal_log.push((target, card.clone()));
+        match target {
+            DealTarget::Player(index) => self.player_hands[index].push(card),
+            DealTarget::Dealer => self.dealer_hand.push(card),
+        }
+    }
+
+    fn deal_log(&self) -> &[(DealTarget, Card)] {
+        &self.deal_log
+    }
+
+    fn draw_card(&mut self) -> Option<Card> {
+        if self.deck.is_empty() {
+            match self.rules.low_deck_policy {
+                LowDeckPolicy::Reshuffle => self.reshuffle_discards(),
+                LowDeckPolicy::VoidRound => return None,
+            }
+        }
+        let card = self.deck.pop();
+        if self.shoe_size > 0 && self.cards_to_cut() == 0 {
+            self.needs_reshuffle = true; // Finish the round, then shuffle
+        }
+        card
+    }
+
+    /// Returns the next `n` cards to be drawn (fewer if the deck is short).
+    /// Draws `pop` from the end of the deck, so the last card of the slice comes out first.
+    fn peek_next(&self, n: usize) -> &[Card] {
+        let start = self.deck.len().saturating_sub(n);
+        &self.deck[start..]
+    }
+
+    fn void_round(&mut self) {
+        self.total_money = self.total_money.saturating_add(self.total_at_risk()); // Return every bet, as on a push
+        self.game_state = GameState::GameOver(String::from("Round Void: Deck depleted, all bets returned."));
+    }
+
+    fn abandon_round(&mut self) {
+        // Give up every hand: nothing is returned and the dealer doesn't play
+        self.flush_pending_advance();
+        if !matches!(self.game_state, GameState::PlayerTurn) {
+            return;
+        }
+        self.record(GameEvent::GiveUp);
+        let value_rules = self.rules.value_rules;
+        self.outcomes = self
+            .player_hands
+            .iter()
+            .map(|hand| if Self::calculate_hand_value(hand, value_rules) > 21 { Outcome::Busted } else { Outcome::Lost })
+            .collect();
+        self.bonus_winnings = vec![0; self.player_hands.len()];
+        self.first_card_hidden = false;
+        self.confirm_abandon = false;
+        self.round_abandoned = true;
+        self.close_round(String::from("Round Over: Gave up, all stakes lost."));
+    }
+
+    fn close_round(&mut self, mut message: String) {
+        self.record(GameEvent::Settle);
+        self.record_round(); // The history keeps the real bankroll, before any top-up
+        self.rounds_played += 1;
+        if let Some(amount) = self.auto_topup {
+            if self.total_money < self.rules.chip_denomination.max(1) {
+                self.total_money = amount;
+                message = format!("{} Bankroll topped up to {}.", message.trim_end(), self.format_money(amount));
+            }
+        }
+        self.goal_result = self.check_win_condition();
+        self.game_state = GameState::GameOver(message);
+    }
+
+    fn is_hand_locked(&self, index: usize) -> bool {
+        let hand = &self.player_hands[index];
+        self.rules.split_aces_locked && self.split_hands[index] && hand[0].value == Value::Ace && hand.len() >= 2
+    }
+
+    fn hit(&mut self) -> Result<(), GameError> {
+        self.flush_pending_advance();
+        if !matches!(self.game_state, GameState::PlayerTurn) || self.is_hand_locked(self.current_hand) {
+            return Err(GameError::IllegalAction);
+        }
+
+        let first_hit = !self.round_actions.contains(&(self.current_hand, Action::Hit));
+        if self.rules.forgiving_first_hit && first_hit {
+            self.stack_forgiving_card();
+        }
+        self.round_actions.push((self.current_hand, Action::Hit));
+        self.record(GameEvent::Hit);
+        self.draw_to_current_hand()
+    }
+
+    fn stack_forgiving_card(&mut self) {
+        // Only steps in when the real next card would bust, then brings up the nearest card that doesn't
+        let hand = &self.player_hands[self.current_hand];
+        let value_rules = self.rules.value_rules;
+        let total_with = |card: &Card| {
+            let mut next = hand.clone();
+            next.push(card.clone());
+            Self::calculate_hand_value(&next, value_rules)
+        };
+        if !matches!(self.deck.last(), Some(top) if total_with(top) > 21) {
+            return;
+        }
+        if let Some(position) = self.deck.iter().rposition(|card| total_with(card) <= 21) {
+            let top = self.deck.len() - 1;
+            self.deck.swap(position, top);
+        }
+    }
+
+    fn draw_to_current_hand(&mut self) -> Result<(), GameError> {
+        let card = match self.draw_card() {
+            Some(card) => card,
+            None => {
+                self.void_round();
+                return Err(GameError::DeckEmpty);
+            }
+        };
+        self.place_card(DealTarget::Player(self.current_hand), card);
+        if self.current_hand_busted() {
+            self.trigger_flash(FlashKind::Bust);
+            self.finish_hand(); // Busted, move to next hand or dealer's turn
+        } else if self.is_charlie(self.current_hand) {
+            self.finish_hand(); // Nothing more to play for
+        } else if self.auto_stand_21 && self.current_hand_value() == 21 {
+            self.finish_hand();
+        }
+        Ok(())
+    }
+
+    fn stand(&mut self) -> Result<(), GameError> {
+        self.flush_pending_advance();
+        if !matches!(self.game_state, GameState::PlayerTurn) {
+            return Err(GameError::IllegalAction); // A settled round must not be settled again
+        }
+        self.round_actions.push((self.current_hand, Action::Stand));
+        self.record(GameEvent::Stand);
+        self.finish_hand();
+        Ok(())
+    }
+
+    fn finish_hand(&mut self) {
+        self.first_card_hidden = false; // Standing, busting or doubling reveals the blind card
+        // Pause on a finished split hand so the player can see how it ended; update() completes the move
+        let more_hands = self.current_hand + 1 < self.player_hands.len();
+        if more_hands && self.advance_delay > 0.0 {
+            self.pending_advance = Some(Instant::now());
+        } else {
+            self.advance_hand();
+        }
+    }
+
+    fn flush_pending_advance(&mut self) {
+        if self.pending_advance.take().is_some() {
+            self.advance_hand();
+        }
+    }
+
+    fn advance_hand(&mut self) {
+        let mut next_hand = self.current_hand + 1;
+        while next_hand < self.player_hands.len() && self.is_hand_locked(next_hand) {
+            next_hand += 1; // Locked hands have nothing left to play
+        }
+        if next_hand < self.player_hands.len() {
+            self.current_hand = next_hand; // Move to the next hand if any
+            self.auto_split_pairs();
+        } else {
+            self.game_state = GameState::DealerTurn; // Move to dealer's turn
+            self.dealer_turn();
+        }
+    }
+
+    fn legal_actions(&self) -> Vec<Action> {
+        if !matches!(self.game_state, GameState::PlayerTurn) {
+            return Vec::new();
+        }
+        if self.is_hand_locked(self.current_hand) {
+            return vec![Action::Stand];
+        }
+
+        let mut actions = vec![Action::Hit, Action::Stand];
+        if self.double_allowed_by_rules() && self.double_stake() > 0 && self.total_money >= self.double_stake() {
+            actions.push(Action::DoubleDown);
+        }
+        if self.can_split_now() {
+            actions.push(Action::Split);
+        }
+        actions
+    }
+
+    fn total_at_risk(&self) -> usize {
+        // Every hand's stake, including doubles and splits
+        self.player_bets.iter().fold(0, |total, &bet| total.saturating_add(bet))
+    }
+
+    fn double_stake(&self) -> usize {
+        // Doubling matches the hand's current bet unless the table lets the player put up less
+        let bet = self.player_bets[self.current_hand];
+        if self.rules.double_for_less {
+            self.rules.snap_to_chips(self.double_amount).min(bet)
+        } else {
+            bet
+        }
+    }
+
+    fn double_allowed_by_rules(&self) -> bool {
+        let after_split_ok = self.rules.double_after_split || !self.split_hands[self.current_hand];
+        after_split_ok && self.double_allowed_on_total()
+    }
+
+    fn double_allowed_on_total(&self) -> bool {
+        let hand = &self.player_hands[self.current_hand];
+        match self.rules.double_restriction {
+            DoubleRule::Any => true,
+            // Restricted tables only count hard totals, so a soft hand never qualifies
+            rule => !Self::is_soft(hand, self.rules.value_rules) && rule.allows(Self::calculate_hand_value(hand, self.rules.value_rules)),
+        }
+    }
+
+    fn double_down(&mut self) -> Result<(), GameError> {
+        self.flush_pending_advance();
+        if !matches!(self.game_state, GameState::PlayerTurn) || self.is_hand_locked(self.current_hand) || !self.double_allowed_by_rules() {
+            return Err(GameError::IllegalAction);
+        }
+        if !self.legal_actions().contains(&Action::DoubleDown) {
+            return Err(GameError::InsufficientFunds);
+        }
+
+        let doubled_hand = self.current_hand;
+        self.round_actions.push((doubled_hand, Action::DoubleDown));
+        let extra_stake = self.double_stake();
+        self.record(GameEvent::Double(extra_stake));
+        self.total_money = self.total_money.saturating_sub(extra_stake);
+        self.player_bets[doubled_hand] = self.player_bets[doubled_hand].saturating_add(extra_stake);
+        self.draw_to_current_hand()?;
+        // A bust has already moved play on, so only advance if we're still on the doubled hand
+        if matches!(self.game_state, GameState::PlayerTurn) && self.current_hand == doubled_hand && self.pending_advance.is_none() {
+            self.finish_hand();
+        }
+        Ok(())
+    }
+
+    fn split(&mut self) -> Result<(), GameError> {
+        self.flush_pending_advance();
+        if !matches!(self.game_state, GameState::PlayerTurn)
+            || self.is_hand_locked(self.current_hand)
+            || self.is_card_hidden(self.current_hand, 0) // A blind pair can't be recognised
+            || !Self::can_split(&self.player_hands[self.current_hand])
+            || !self.split_rank_allowed()
+            || !self.within_split_caps()
+        {
+            return Err(GameError::IllegalAction);
+        }
+        if !self.can_split_now() {
+            return Err(GameError::InsufficientFunds);
+        }
+
+        let (first_draw, second_draw) = match (self.draw_card(), self.draw_card()) {
+            (Some(first), Some(second)) => (first, second),
+            _ => {
+                self.void_round();
+                return Err(GameError::DeckEmpty);
+            }
+        };
+
+        self.round_actions.push((self.current_hand, Action::Split));
+        self.record(GameEvent::Split);
+        let bet_for_new_hand = self.player_bets[self.current_hand];
+        self.total_money = self.total_money.saturating_sub(bet_for_new_hand);
+        self.player_bets.push(bet_for_new_hand);
+
+        let card_for_new_hand = self.player_hands[self.current_hand].pop().unwrap(); // Pair checked above
+        self.place_card(DealTarget::Player(self.current_hand), first_draw);
+        self.player_hands.push(vec![card_for_new_hand]);
+        self.place_card(DealTarget::Player(self.player_hands.len() - 1), second_draw);
+        self.split_hands[self.current_hand] = true;
+        self.split_hands.push(true);
+        if self.is_hand_locked(self.current_hand) {
+            self.finish_hand();
+        }
+        Ok(())
+    }
+
+    fn is_blackjack(hand: &[Card], value_rules: ValueRules) -> bool {
+        hand.len() == 2 && Self::calculate_hand_value(hand, value_rules) == 21
+    }
+
+    fn is_natural(&self, index: usize) -> bool {
+        // A 21 made after splitting only counts as a regular 21
+        !self.split_hands[index] && Self::is_blackjack(&self.player_hands[index], self.rules.value_rules)
+    }
+
+    fn is_charlie(&self, index: usize) -> bool {
+        let hand = &self.player_hands[index];
+        matches!(self.rules.charlie, Some(cards) if hand.len() >= cards) && Self::calculate_hand_value(hand, self.rules.value_rules) <= 21
+    }
+
+    #[cfg(test)]
+    fn add_bonus(&mut self, bonus: Box<dyn HandBonus>) {
+        self.custom_bonuses.push(bonus);
+    }
+
+    #[cfg(test)]
+    fn set_settlement_hook(&mut self, hook: SettlementHook) {
+        self.settlement_hook = Some(hook);
+    }
+
+    fn best_bonus(&self, index: usize) -> Option<(String, usize)> {
+        let hand = &self.player_hands[index];
+        let split = self.split_hands[index];
+        let built_in = self.rules.bonuses.iter().map(|kind| kind.bonus());
+        built_in
+            .filter(|bonus| bonus.matches(hand, split, self.rules.value_rules))
+            .map(|bonus| (bonus.name(), bonus.pays()))
+            .chain(
+                self.custom_bonuses
+                    .iter()
+                    .filter(|bonus| bonus.matches(hand, split, self.rules.value_rules))
+                    .map(|bonus| (bonus.name(), bonus.pays())),
+            )
+            .max_by_key(|&(_, pays)| pays)
+    }
+
+    fn dealer_outcome_distribution(&self, upcard: &Card) -> [f64; 6] {
+        // Chances of the dealer finishing on 17, 18, 19, 20, 21 or busting, drawing from the cards the player
+        // hasn't seen: the shoe, the burned cards and the hole card. Follows the table's stand thresholds,
+        // so a dealer standing below 17 lands in the first slot. Beat-the-hand dealers depend on the hands instead.
+        fn draw(counts: &mut [usize; 10], hard: usize, has_ace: bool, chance: f64, rules: &RuleSet, result: &mut [f64; 6]) {
+            let soft = has_ace && !rules.value_rules.aces_low_only && hard + 10 <= 21;
+            let total = if soft { hard + 10 } else { hard };
+            if total > 21 {
+                result[5] += chance;
+                return;
+            }
+            let remaining: usize = counts.iter().sum();
+            if total >= rules.dealer_threshold.stand_at(soft) || remaining == 0 {
+                result[total.saturating_sub(17)] += chance;
+                return;
+            }
+            for rank in 0..10 {
+                if counts[rank] == 0 {
+                    continue;
+                }
+                let p = counts[rank] as f64 / remaining as f64;
+                counts[rank] -= 1;
+                draw(counts, hard + rank + 1, has_ace || rank == 0, chance * p, rules, result);
+                counts[rank] += 1;
+            }
+        }
+
+        let rank = |card: &Card| if card.value == Value::Ace { 1 } else { card.value() as usize };
+        let mut counts = [0; 10]; // Ace through 9, then every ten-value card
+        for card in self.deck.iter().chain(self.burned.iter()).chain(self.dealer_hand.get(1)) {
+            counts[rank(card) - 1] += 1;
+        }
+        let mut result = [0.0; 6];
+        draw(&mut counts, rank(upcard), upcard.value == Value::Ace, 1.0, &self.rules, &mut result);
+        result
+    }
+
+    fn dealer_has_natural(&self) -> bool {
+        Self::is_blackjack(&self.dealer_hand, self.rules.value_rules)
+    }
+
+    fn dealer_turn(&mut self) {
+        let value_rules = self.rules.value_rules;
+        if self.dealer_hand.len() < 2 {
+            // No-hole-card deal: the dealer's second card only arrives now
+            match self.draw_card() {
+                Some(card) => self.place_dealer_draw(card),
+                None => {
+                    self.void_round();
+                    return;
+                }
+            }
+        }
+        if self.player_hands.iter().all(|hand| Self::calculate_hand_value(hand, value_rules) > 21) {
+            let line = format!("Dealer has {}, every hand busted so no need to draw", self.dealer_total_words());
+            self.dealer_narration.push(line);
+            self.evaluate_game_outcomes(); // Every hand has already lost, the dealer doesn't draw
+            return;
+        }
+        let settled = |index: usize| Self::calculate_hand_value(&self.player_hands[index], value_rules) > 21 || self.is_natural(index);
+        if (0..self.player_hands.len()).all(settled) {
+            let line = format!("Dealer has {}, nothing left to draw against", self.dealer_total_words());
+            self.dealer_narration.push(line);
+            self.evaluate_game_outcomes(); // Drawing can't change a bust or a natural
+            return;
+        }
+
+        let dealer_rules = self.rules.dealer_play.rules(self.rules.dealer_threshold);
+        while dealer_rules.should_hit(&self.dealer_hand, &self.player_hands, value_rules) {
+            let line = format!("Dealer has {}, must hit", self.dealer_total_words());
+            self.dealer_narration.push(line);
+            if let Some(card) = self.draw_card() {
+                self.place_dealer_draw(card);
+            } else {
+                self.void_round(); // Never settle against an unfinished dealer hand
+                return;
+            }
+        }
+
+        let dealer_value = Self::calculate_hand_value(&self.dealer_hand, value_rules);
+        let line = if dealer_value > 21 {
+            format!("Dealer busts with {}", dealer_value)
+        } else {
+            format!("Dealer has {}, stands", self.dealer_total_words())
+        };
+        self.dealer_narration.push(line);
+        let stand_at = self.rules.dealer_threshold.stand_at(Self::is_soft(&self.dealer_hand, value_rules));
+        debug_assert!(
+            self.rules.dealer_play != DealerPlay::Standard || dealer_value >= stand_at,
+            "dealer stood on {} but should stand on {}",
+            dealer_value,
+            stand_at
+        );
+        self.evaluate_game_outcomes();
+    }
+
+    fn place_dealer_draw(&mut self, card: Card) {
+        self.record(GameEvent::DealerDraw(card.clone()));
+        self.place_card(DealTarget::Dealer, card.clone());
+        let total = Self::calculate_hand_value(&self.dealer_hand, self.rules.value_rules);
+        self.dealer_draws.push((card, total));
+    }
+
+    fn dealer_total_words(&self) -> String {
+        let value = Self::calculate_hand_value(&self.dealer_hand, self.rules.value_rules);
+        if Self::is_soft(&self.dealer_hand, self.rules.value_rules) {
+            format!("soft {}", value)
+        } else {
+            value.to_string()
+        }
+    }
+
+    fn dealer_play_summary(&self) -> String {
+        // e.g. "K + 6 = 16, hit 7 = 23, bust" from the cards drawn during the dealer's turn
+        let value_rules = self.rules.value_rules;
+        // A no-hole-card dealer draws the second card on their turn, but it's still part of the deal
+        let dealt = (self.dealer_hand.len() - self.dealer_draws.len()).max(2).min(self.dealer_hand.len());
+        let opening = &self.dealer_hand[..dealt];
+        let mut summary = format!(
+            "{} = {}",
+            opening.iter().map(|c| c.short()).collect::<Vec<_>>().join(" + "),
+            Self::calculate_hand_value(opening, value_rules)
+        );
+        let drawn = self.dealer_hand.len() - dealt;
+        for (card, total) in &self.dealer_draws[self.dealer_draws.len() - drawn..] {
+            summary.push_str(&format!(", hit {} = {}", card.short(), total));
+        }
+        let final_total = Self::calculate_hand_value(&self.dealer_hand, value_rules);
+        summary.push_str(if final_total > 21 {
+            ", bust"
+        } else if !self.outcomes.is_empty() && self.outcomes.iter().all(|&outcome| outcome == Outcome::Busted) {
+            ", nothing left to beat" // The dealer doesn't draw against busted hands
+        } else {
+            ", stands"
+        });
+        summary
+    }
+
+    fn calculate_hand_value(hand: &[Card], value_rules: ValueRules) -> usize {
+        let mut value = 0;
+        let mut aces = 0;
+
+        for card in hand {
+            match card.value {
+                Value::Ace => aces += 1,
+                _ => value += card.value() as usize,
+            }
+        }
+
+        value += aces; // Every ace counts at least 1
+        if aces > 0 && !value_rules.aces_low_only && value + 10 <= 21 {
+            value += 10; // Only one ace can ever count as 11
+        }
+
+        value
+    }
+
+    fn current_hand_value(&self) -> usize {
+        Self::calculate_hand_value(&self.player_hands[self.current_hand], self.rules.value_rules)
+    }
+
+    fn reachable_totals(&self) -> Vec<(usize, f64)> {
+        // Every total one more card could make, with its chance given what's left in the shoe
+        let mut totals: Vec<(usize, f64)> = Vec::new();
+        let mut next = self.player_hands[self.current_hand].clone();
+        for card in &self.deck {
+            next.push(card.clone());
+            let total = Self::calculate_hand_value(&next, self.rules.value_rules);
+            next.pop();
+            let share = 1.0 / self.deck.len() as f64;
+            match totals.iter_mut().find(|(t, _)| *t == total) {
+                Some((_, chance)) => *chance += share,
+                None => totals.push((total, share)),
+            }
+        }
+        totals.sort_by_key(|&(total, _)| total);
+        totals
+    }
+
+    fn current_hand_is_soft(&self) -> bool {
+        Self::is_soft(&self.player_hands[self.current_hand], self.rules.value_rules)
+    }
+
+    fn current_hand_busted(&self) -> bool {
+        self.current_hand_value() > 21
+    }
+
+    fn dealer_value(&self) -> usize {
+        // While the player acts only the upcard counts, unless the hole card is being shown
+        if matches!(self.game_state, GameState::PlayerTurn) && !self.reveal_hole_card {
+            Self::calculate_hand_value(&self.dealer_hand[..1], self.rules.value_rules)
+        } else {
+            Self::calculate_hand_value(&self.dealer_hand, self.rules.value_rules)
+        }
+    }
+
+    fn describe_state(&self) -> String {
+        let value_rules = self.rules.value_rules;
+        let cards = |hand: &[Card]| hand.iter().map(|c| c.short()).collect::<Vec<_>>().join(" ");
+
+        match &self.game_state {
+            GameState::Startup => String::from("A saved game was found. Continue it or start a new session."),
+            GameState::Betting => format!(
+                "Betting {}. Bankroll {}.",
+                self.format_money(self.wager),
+                self.format_money(self.total_money)
+            ),
+            GameState::PlayerTurn => {
+                let hand = self.player_card_views(self.current_hand).iter().map(|view| view.label()).collect::<Vec<_>>().join(" ");
+                let actions = self.legal_actions().iter().map(|a| a.label()).collect::<Vec<_>>().join(", ");
+                format!(
+                    "Your turn, hand {} of {}: {}, total {}. Bet {}. Dealer shows {}. You can: {}.",
+                    self.current_hand + 1,
+                    self.player_hands.len(),
+                    hand,
+                    self.player_total_label(self.current_hand),
+                    self.format_money(self.player_bets[self.current_hand]),
+                    self.dealer_hand[0].short(),
+                    actions
+                )
+            }
+            GameState::DealerTurn => String::from("Dealer's turn."),
+            GameState::GameOver(message) => {
+                let hands = self
+                    .player_hands
+                    .iter()
+                    .enumerate()
+                    .map(|(index, hand)| format!("hand {} {} = {}", index + 1, cards(hand), Self::total_label(hand, value_rules)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!(
+                    "{} Dealer {} = {}. You had {}. Bankroll {}.",
+                    message.trim_end(),
+                    cards(&self.dealer_hand),
+                    Self::total_label(&self.dealer_hand, value_rules),
+                    hands,
+                    self.format_money(self.total_money)
+                )
+            }
+        }
+    }
+
+    fn is_card_hidden(&self, index: usize, card: usize) -> bool {
+        self.first_card_hidden && index == 0 && card == 0 && matches!(self.game_state, GameState::PlayerTurn)
+    }
+
+    fn player_card_views(&self, index: usize) -> Vec<CardView<'_>> {
+        self.player_hands[index]
+            .iter()
+            .enumerate()
+            .map(|(card, value)| if self.is_card_hidden(index, card) { CardView::FaceDown } else { CardView::FaceUp(value) })
+            .collect()
+    }
+
+    fn should_show_player_total(&self) -> bool {
+        // Practising players add up their own cards until the round is settled
+        self.show_player_total || !matches!(self.game_state, GameState::PlayerTurn)
+    }
+
+    fn should_show_shoe_count(&self) -> bool {
+        if self.rules.csm {
+            return false; // The shoe is always full, there's nothing to count
+        }
+        match self.shoe_count_display {
+            ShoeCountDisplay::Always => true,
+            ShoeCountDisplay::BetweenRounds => !matches!(self.game_state, GameState::PlayerTurn | GameState::DealerTurn),
+            ShoeCountDisplay::AfterReshuffle => {
+                // At most the first round of the shoe has been cleared away
+                !matches!(self.game_state, GameState::PlayerTurn | GameState::DealerTurn) && self.discards.is_empty()
+            }
+        }
+    }
+
+    fn player_total_label(&self, index: usize) -> String {
+        if self.is_card_hidden(index, 0) {
+            return String::from("?"); // The total would give the blind card away
+        }
+        if !self.should_show_player_total() {
+            return String::from("?");
+        }
+        Self::total_label(&self.player_hands[index], self.rules.value_rules)
+    }
+
+    fn is_soft(hand: &[Card], value_rules: ValueRules) -> bool {
+        // Soft when an ace is currently being counted as 11
+        let hard_value: usize = hand.iter().map(|c| if c.value == Value::Ace { 1 } else { c.value() as usize }).sum();
+        Self::calculate_hand_value(hand, value_rules) != hard_value
+    }
+
+    fn total_label(hand: &[Card], value_rules: ValueRules) -> String {
+        let kind = if Self::is_soft(hand, value_rules) { "soft" } else { "hard" };
+        format!("{} ({})", Self::calculate_hand_value(hand, value_rules), kind)
+    }
+
+    fn evaluate_game_outcomes(&mut self) {
+        let dealer_value = Self::calculate_hand_value(&self.dealer_hand, self.rules.value_rules);
+        let dealer_bust = dealer_value > 21;
+        let dealer_natural = self.dealer_has_natural();
+        let mut message = String::from("Round Over: ");
+        self.outcomes.clear();
+        self.bonus_winnings = vec![0; self.player_hands.len()];
+        if let Err(problem) = self.check_consistency() {
+            self.recover_round(&problem, self.total_money);
+            return;
+        }
+        let bankroll_before = self.total_money;
+
+        for index in 0..self.player_hands.len() {
+            let hand_value = Self::calculate_hand_value(&self.player_hands[index], self.rules.value_rules);
+            let hooked = match &self.settlement_hook {
+                Some(hook) if hand_value <= 21 => hook(&self.player_hands[index], &self.dealer_hand),
+                _ => None, // A bust is always a bust
+            };
+            let natural = self.is_natural(index);
+            let outcome = if let Some(outcome) = hooked {
+                outcome
+            } else if natural && dealer_natural {
+                Outcome::BlackjackPush
+            } else if natural {
+                Outcome::Blackjack // Beats every dealer hand but a natural, including a drawn 21
+            } else if hand_value > 21 {
+                Outcome::Busted
+            } else if dealer_natural {
+                Outcome::Lost // A dealer blackjack beats any other 21, and takes doubled and split stakes in full
+            } else if self.is_charlie(index) {
+                Outcome::Won
+            } else if self.rules.dealer_22_pushes && dealer_value == 22 {
+                Outcome::Push
+            } else if hand_value > dealer_value || dealer_bust {
+                Outcome::Won
+            } else if hand_value < dealer_value {
+                Outcome::Lost
+            } else {
+                Outcome::Push
+            };
+
+            let bet = self.player_bets[index];
+            match outcome {
+                // Saturating, so a long simulation pins the bankroll at the limit rather than wrapping
+                Outcome::Won | Outcome::Blackjack => self.total_money = self.total_money.saturating_add(bet.saturating_mul(2)), // Win double the bet
+                Outcome::Push | Outcome::BlackjackPush => self.total_money = self.total_money.saturating_add(bet), // Return the bet
+                Outcome::Lost | Outcome::Busted => {}
+            }
+            message.push_str(&format!("Hand {} {} ", index + 1, outcome.label()));
+            // The natural's extra half is paid like a bonus on top of even money; a richer bonus replaces it
+            let mut extra = if outcome == Outcome::Blackjack { self.rules.natural_winnings(bet) - bet } else { 0 };
+            if let (Outcome::Won | Outcome::Blackjack, Some((name, pays))) = (outcome, self.best_bonus(index)) {
+                let bonus = bet.saturating_mul(pays.saturating_sub(1));
+                if bonus > extra {
+                    extra = bonus;
+                    message.push_str(&format!("({} pays {}:1) ", name, pays));
+                }
+            }
+            self.total_money = self.total_money.saturating_add(extra);
+            self.bonus_winnings[index] = extra;
+            self.outcomes.push(outcome);
+        }
+
+        if let Err(problem) = self.check_consistency() {
+            self.recover_round(&problem, bankroll_before);
+            return;
+        }
+        self.chip_animation = if self.animate_deal { Some(Instant::now()) } else { None }; // Instant deals settle instantly too
+        self.close_round(message);
+    }
+
+    fn check_consistency(&self) -> Result<(), String> {
+        let hands = self.player_hands.len();
+        if self.player_bets.len() != hands || self.split_hands.len() != hands {
+            return Err(format!("{} hands but {} bets and {} split flags", hands, self.player_bets.len(), self.split_hands.len()));
+        }
+        if !self.outcomes.is_empty() && self.outcomes.len() != hands {
+            return Err(format!("{} hands but {} outcomes", hands, self.outcomes.len()));
+        }
+        for (index, outcome) in self.outcomes.iter().enumerate() {
+            let busted = Self::calculate_hand_value(&self.player_hands[index], self.rules.value_rules) > 21;
+            if busted != (*outcome == Outcome::Busted) {
+                return Err(format!("hand {} settled as {:?}", index + 1, outcome));
+            }
+        }
+        Ok(())
+    }
+
+    fn recover_round(&mut self, problem: &str, bankroll_before: usize) {
+        // Undo any payouts and hand back the stakes rather than keep a corrupt settlement
+        eprintln!("Inconsistent round state, resetting round: {}", problem);
+        self.total_money = bankroll_before.saturating_add(self.total_at_risk());
+        self.player_bets.resize(self.player_hands.len(), 0);
+        self.split_hands.resize(self.player_hands.len(), false);
+        self.outcomes.clear();
+        self.game_state = GameState::GameOver(String::from("Round Reset: Something went wrong, all bets returned."));
+    }
+
+    fn format_money(&self, amount: usize) -> String {
+        // Every amount on screen goes through here, so the symbol, separator and cap apply everywhere
+        let capped = self.money_format.display_cap.filter(|&cap| amount > cap);
+        let digits = capped.unwrap_or(amount).to_string();
+        let mut grouped = String::new();
+
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                grouped.push(self.money_format.separator); // Thousands separator
+            }
+            grouped.push(digit);
+        }
+
+        let over = if capped.is_some() { "+" } else { "" };
+        format!("{}{}{}", self.money_format.symbol, grouped, over)
+    }
+
+    fn format_net(&self, net: i64) -> String {
+        let sign = if net < 0 { "-" } else { "+" };
+        format!("{}{}", sign, self.format_money(net.unsigned_abs() as usize))
+    }
+
+    fn hand_net(outcome: Outcome, bet: usize) -> i64 {
+        let bet = i64::try_from(bet).unwrap_or(i64::MAX);
+        match outcome {
+            Outcome::Won | Outcome::Blackjack => bet,
+            Outcome::Push | Outcome::BlackjackPush => 0,
+            Outcome::Lost | Outcome::Busted => -bet,
+        }
+    }
+
+    fn record_round(&mut self) {
+        const MAX_HISTORY: usize = 100; // Oldest rounds are dropped beyond this
+
+        let net: i64 = self
+            .outcomes
+            .iter()
+            .zip(self.player_bets.iter())
+            .map(|(&outcome, &bet)| Self::hand_net(outcome, bet))
+            .chain(self.bonus_winnings.iter().map(|&extra| i64::try_from(extra).unwrap_or(i64::MAX)))
+            .fold(0, i64::saturating_add);
+        self.best_net = self.best_net.max(net);
+        self.worst_net = self.worst_net.min(net);
+        match self.shoe_results.last_mut() {
+            Some(shoe_net) => *shoe_net = shoe_net.saturating_add(net),
+            None => self.shoe_results.push(net), // Restored or scripted mid-shoe
+        }
+        let round_start = self.events[..self.events.len().saturating_sub(1)] // This round's own Settle is last
+            .iter()
+            .rposition(|event| matches!(event, GameEvent::Settle))
+            .map_or(0, |settle| settle + 1);
+        self.round_history.push(RoundRecord {
+            bets: self.player_bets.clone(),
+            player_hands: self.player_hands.clone(),
+            dealer_hand: self.dealer_hand.clone(),
+            outcomes: self.outcomes.clone(),
+            actions: self.round_actions.clone(),
+            net,
+            bankroll: self.total_money,
+            bad_beat: self.outcomes.iter().zip(self.player_hands.iter()).any(|(&outcome, hand)| {
+                outcome == Outcome::Lost && Self::is_bad_beat(hand, &self.dealer_hand, self.rules.value_rules)
+            }),
+            true_count: self.opening_count,
+            events: self.events[round_start..].to_vec(),
+        });
+        if self.round_history.len() > MAX_HISTORY {
+            self.round_history.remove(0);
+        }
+    }
+
+    fn is_bad_beat(player: &[Card], dealer: &[Card], value_rules: ValueRules) -> bool {
+        // A 20 caught by a drawn 21, or any 19 or better overtaken after the dealer drew two or more cards
+        let player_value = Self::calculate_hand_value(player, value_rules);
+        let dealer_value = Self::calculate_hand_value(dealer, value_rules);
+        if player_value > 21 || dealer_value > 21 || dealer_value <= player_value {
+            return false;
+        }
+        let dealer_drew = dealer.len() > 2;
+        (player_value >= 20 && dealer_value == 21 && dealer_drew) || (player_value >= 19 && dealer.len() >= 4)
+    }
+
+    fn hands_per_hour(&self) -> f64 {
+        Self::hands_per_hour_over(self.rounds_played, self.session_start.elapsed())
+    }
+
+    fn hands_per_hour_over(rounds: usize, elapsed: Duration) -> f64 {
+        let hours = elapsed.as_secs_f64() / 3600.0;
+        if hours <= 0.0 {
+            return 0.0; // Nothing meaningful before any time has passed
+        }
+        rounds as f64 / hours
+    }
+
+    fn place_bet(&mut self) -> Result<(), GameError> {
+        if !matches!(self.game_state, GameState::Betting | GameState::GameOver(_)) {
+            return Err(GameError::IllegalAction);
+        }
+        let wager = self.rules.snap_to_chips(self.wager);
+        if wager > self.total_money {
+            return Err(GameError::InsufficientFunds);
+        }
+        self.wager = wager;
+        self.remember_bet(wager);
+        self.new_round();
+        Ok(())
+    }
+
+    fn parse_bet(&self, text: &str) -> Result<usize, String> {
+        let text = text.trim();
+        if text.starts_with('-') {
+            return Err(String::from("Bets can't be negative"));
+        }
+        let amount: usize = text.parse().map_err(|_| String::from("Enter a whole number"))?;
+        let chip = self.rules.chip_denomination.max(1);
+        if amount < chip {
+            Err(format!("The table minimum is {}", self.format_money(chip)))
+        } else if !amount.is_multiple_of(chip) {
+            Err(format!("Bets go down in chips of {}", self.format_money(chip)))
+        } else if amount > self.total_money {
+            Err(format!("You only have {}", self.format_money(self.total_money)))
+        } else {
+            Ok(amount)
+        }
+    }
+
+    fn remember_bet(&mut self, amount: usize) {
+        const MAX_RECENT_BETS: usize = 5;
+
+        self.recent_bets.retain(|&bet| bet != amount); // A repeated amount moves to the front instead of crowding out others
+        self.recent_bets.push(amount);
+        if self.recent_bets.len() > MAX_RECENT_BETS {
+            self.recent_bets.remove(0);
+        }
+    }
+
+    fn current_shoe_net(&self) -> i64 {
+        self.shoe_results.last().copied().unwrap_or(0)
+    }
+
+    fn previous_round_summary(&self) -> Option<String> {
+        // Context for the next bet; gone once a new game clears the history
+        let record = self.round_history.last()?;
+        let outcomes = record.outcomes.iter().map(|outcome| format!("{:?}", outcome)).collect::<Vec<_>>().join(", ");
+        Some(format!("Previous round: {}, net {}", outcomes, self.format_net(record.net)))
+    }
+
+    fn best_round(&self) -> i64 {
+        self.best_net
+    }
+
+    fn worst_round(&self) -> i64 {
+        self.worst_net
+    }
+
+    fn stats_to_csv(&self) -> String {
+        fn field(text: &str) -> String {
+            if text.contains(&[',', '"', '\n', '\r'][..]) {
+                format!("\"{}\"", text.replace('"', "\"\""))
+            } else {
+                text.to_string()
+            }
+        }
+
+        let mut csv = String::from("round,bet,outcome,net,bankroll\n");
+        for (number, record) in self.round_history.iter().enumerate() {
+            let bet: usize = record.bets.iter().sum();
+            let outcome = record.outcomes.iter().map(|outcome| format!("{:?}", outcome)).collect::<Vec<_>>().join(", "); // One per hand
+            csv.push_str(&format!("{},{},{},{},{}\n", number + 1, bet, field(&outcome), record.net, record.bankroll));
+        }
+        csv
+    }
+
+    fn snapshot(&self) -> GameSnapshot {
+        GameSnapshot {
+            deck: self.deck.clone(),
+            burned: self.burned.clone(),
+            discards: Some(self.discards.clone()),
+            needs_reshuffle: self.needs_reshuffle,
+            shoe_rounds: self.shoe_rounds,
+            shoe_size: self.shoe_size,
+            player_hands: self.player_hands.clone(),
+            dealer_hand: self.dealer_hand.clone(),
+            current_hand: self.current_hand,
+            player_bets: self.player_bets.clone(),
+            split_hands: self.split_hands.clone(),
+            total_money: self.total_money,
+            game_state: self.game_state.clone(),
+            outcomes: self.outcomes.clone(),
+            round_actions: self.round_actions.clone(),
+            seed: self.seed,
+            shuffles: self.shuffles,
+            advancing: self.pending_advance.is_some(),
+            events: self.events.clone(),
+            wager: self.wager,
+            first_card_hidden: self.first_card_hidden,
+            bonus_winnings: self.bonus_winnings.clone(),
+            dealer_draws: self.dealer_draws.clone(),
+            round_history: self.round_history.clone(),
+            rounds_played: self.rounds_played,
+            best_net: self.best_net,
+            worst_net: self.worst_net,
+            shoe_results: self.shoe_results.clone(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: GameSnapshot) {
+        // Settings are left as they are
+        self.deck = snapshot.deck;
+        self.burned = snapshot.burned;
+        self.needs_reshuffle = snapshot.needs_reshuffle || snapshot.discards.is_none();
+        self.discards = snapshot.discards.unwrap_or_default();
+        self.shoe_rounds = snapshot.shoe_rounds;
+        self.shoe_size = snapshot.shoe_size;
+        self.player_hands = snapshot.player_hands;
+        self.dealer_hand = snapshot.dealer_hand;
+        self.current_hand = snapshot.current_hand;
+        self.player_bets = snapshot.player_bets;
+        self.split_hands = snapshot.split_hands;
+        self.total_money = snapshot.total_money;
+        self.game_state = snapshot.game_state;
+        self.outcomes = snapshot.outcomes;
+        self.round_actions = snapshot.round_actions;
+        self.seed = snapshot.seed;
+        self.shuffles = snapshot.shuffles;
+        self.pending_advance = if snapshot.advancing { Some(Instant::now()) } else { None };
+        self.events = snapshot.events;
+        self.logged_rules = self.rules.clone();
+        self.wager = match snapshot.wager {
+            0 => self.player_bets.first().copied().unwrap_or(10),
+            wager => wager,
+        };
+        self.first_card_hidden = snapshot.first_card_hidden;
+        self.bonus_winnings = snapshot.bonus_winnings;
+        self.dealer_draws = snapshot.dealer_draws;
+        self.round_history = snapshot.round_history;
+        self.rounds_played = snapshot.rounds_played;
+        self.best_net = snapshot.best_net;
+        self.worst_net = snapshot.worst_net;
+        self.shoe_results = snapshot.shoe_results;
+        self.last_error = None;
+        self.flash = None;
+    }
+
+    fn record(&mut self, event: GameEvent) {
+        self.log_rule_changes(); // A replay has to reach this event under the same rules
+        self.events.push(event);
+    }
+
+    fn log_rule_changes(&mut self) {
+        if self.rules != self.logged_rules {
+            self.logged_rules = self.rules.clone();
+            self.events.push(GameEvent::Rules(self.rules.clone()));
+        }
+    }
+
+    #[cfg(test)]
+    fn events(&self) -> &[GameEvent] {
+        &self.events
+    }
+
+    fn apply_event(&mut self, event: GameEvent) -> Result<(), GameError> {
+        // Each action records its own event again, so a replayed game ends up with the same log
+        match event {
+            GameEvent::Bet(amount) => {
+                self.wager = amount;
+                self.place_bet()?;
+            }
+            GameEvent::Hit => self.hit()?,
+            GameEvent::Stand => self.stand()?,
+            GameEvent::Split => self.split()?,
+            GameEvent::Double(extra_stake) => {
+                self.double_amount = extra_stake;
+                self.double_down()?;
+            }
+            GameEvent::GiveUp => self.abandon_round(),
+            GameEvent::Reshuffle => self.reshuffle_now(),
+            GameEvent::Rules(rules) => {
+                if rules.num_decks != self.rules.num_decks {
+                    self.needs_reshuffle = true; // As the settings slider does
+                }
+                self.rules = rules;
+                self.log_rule_changes();
+            }
+            GameEvent::ForceUpcard(value) => self.force_dealer_upcard(value),
+            GameEvent::DealerDraw(_) | GameEvent::Settle => {}
+        }
+        Ok(())
+    }
+
+    fn replay_events(seed: u64, rules: RuleSet, events: &[GameEvent]) -> Result<BlackjackApp, GameError> {
+        // Rebuilds a game from its seed; rules and play settings must be the ones it was played under
+        let mut app = Self::undealt(seed, rules);
+        for event in events {
+            app.apply_event(event.clone())?;
+        }
+        Ok(app)
+    }
+
+    fn export_session(&self) -> String {
+        let session = SessionExport {
+            seed: self.seed,
+            rules: self.rules.clone(),
+            rounds: self.round_history.clone(),
+        };
+        serde_json::to_string_pretty(&session).expect("session should serialize")
+    }
+
+    fn import_session(json: &str) -> BlackjackApp {
+        // Replays the recorded rounds from the seed, ending in Betting with their bankroll and history
+        let session = match serde_json::from_str::<SessionExport>(json) {
+            Ok(session) => session,
+            Err(err) => {
+                eprintln!("Invalid session file, starting a fresh session: {}", err);
+                return BlackjackApp::new();
+            }
+        };
+        let events = session.rounds.iter().flat_map(|round| round.events.iter().cloned()).collect::<Vec<_>>();
+        match Self::replay_events(session.seed, session.rules.clone(), &events) {
+            Ok(mut app) => {
+                if matches!(app.game_state, GameState::GameOver(_)) && app.goal_result.is_none() {
+                    app.game_state = GameState::Betting; // As Play Again leaves it
+                }
+                app
+            }
+            Err(err) => {
+                eprintln!("Session doesn't replay from its seed, starting it over: {}", err);
+                Self::undealt(session.seed, session.rules)
+            }
+        }
+    }
+
+    fn load_session(&mut self, json: &str) {
+        let imported = Self::import_session(json);
+        self.rules = imported.rules.clone(); // The session's own rules; display and training settings stay
+        self.restore(imported.snapshot());
+    }
+
+    fn counterfactual_stand(&self, decision_index: usize) -> Option<Outcome> {
+        // Settles the hand as if it had stood instead of taking this action. The cards it drew from then
+        // on go to the dealer first, ahead of the dealer's own draws, as they would have at the table.
+        // Only hits and doubles can be undone; splits reshape the round too much.
+        let value_rules = self.rules.value_rules;
+        let &(hand_index, action) = self.round_actions.get(decision_index)?;
+        if !matches!(action, Action::Hit | Action::DoubleDown) {
+            return None;
+        }
+        let later_actions = &self.round_actions[decision_index..];
+        if later_actions.contains(&(hand_index, Action::Split)) {
+            return None;
+        }
+        let undone = later_actions
+            .iter()
+            .filter(|&&(index, action)| index == hand_index && matches!(action, Action::Hit | Action::DoubleDown))
+            .count();
+        let drawn = &self.player_hands[hand_index];
+        let kept = drawn.len().checked_sub(undone)?;
+
+        let mut hands = self.player_hands.clone();
+        hands[hand_index].truncate(kept);
+        let mut dealer = self.dealer_hand[..self.dealer_hand.len() - self.dealer_draws.len()].to_vec();
+        let mut upcoming = drawn[kept..]
+            .iter()
+            .chain(self.dealer_draws.iter().map(|(card, _)| card))
+            .chain(self.deck.iter().rev()) // The top of the deck is its end
+            .cloned();
+        while dealer.len() < 2 && self.rules.hole_card == HoleCard::EuropeanNHC {
+            dealer.push(upcoming.next()?);
+        }
+        let dealer_rules = self.rules.dealer_play.rules(self.rules.dealer_threshold);
+        while dealer_rules.should_hit(&dealer, &hands, value_rules) {
+            dealer.push(upcoming.next()?);
+        }
+
+        let hand = &hands[hand_index];
+        let hand_value = Self::calculate_hand_value(hand, value_rules);
+        let dealer_value = Self::calculate_hand_value(&dealer, value_rules);
+        let natural = |cards: &[Card]| Self::is_blackjack(cards, value_rules);
+        let player_natural = natural(hand) && !self.split_hands[hand_index];
+        Some(if natural(&dealer) && !player_natural {
+            Outcome::Lost
+        } else if player_natural && natural(&dealer) {
+            Outcome::BlackjackPush
+        } else if player_natural {
+            Outcome::Blackjack
+        } else if self.rules.dealer_22_pushes && dealer_value == 22 {
+            Outcome::Push
+        } else if hand_value > dealer_value || dealer_value > 21 {
+            Outcome::Won
+        } else if hand_value < dealer_value {
+            Outcome::Lost
+        } else {
+            Outcome::Push
+        })
+    }
+
+    fn explain_outcome(&self, index: usize) -> String {
+        let hand_value = Self::calculate_hand_value(&self.player_hands[index], self.rules.value_rules);
+        let dealer_value = Self::calculate_hand_value(&self.dealer_hand, self.rules.value_rules);
+
+        match self.outcomes[index] {
+            Outcome::Busted => format!("You went over 21 with {}.", hand_value),
+            Outcome::Lost if self.round_abandoned => String::from("You gave up the round."),
+            Outcome::Push if dealer_value == 22 => String::from("Dealer made 22, which pushes at this table."),
+            Outcome::Push => format!("You and the dealer both had {}.", hand_value),
+            Outcome::BlackjackPush => String::from("You and the dealer were both dealt blackjack."),
+            Outcome::Blackjack => format!("Your blackjack beats the dealer's {} and pays 3:2.", dealer_value),
+            Outcome::Won if self.is_charlie(index) => format!("You drew {} cards without busting.", self.player_hands[index].len()),
+            Outcome::Lost if self.dealer_has_natural() => format!("Dealer had blackjack, which beats your {}.", hand_value),
+            _ if dealer_value > 21 => format!("Dealer busted with {}, you had {}.", dealer_value, hand_value),
+            _ => format!("Dealer had {}, you had {}.", dealer_value, hand_value),
+        }
+    }
+}
+
+impl BlackjackApp {
+    fn hand_row(&self, ui: &mut egui::Ui, heading: egui::Label, cards: &[CardView], id: impl std::hash::Hash) {
+        ui.horizontal(|ui| {
+            ui.add(heading); // Kept outside the scroll area so the total never scrolls away
+            if self.fan_cards {
+                Self::render_hand_fan(ui, cards, self.card_theme);
+                return;
+            }
+            egui::ScrollArea::horizontal().id_source(id).show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    for card in cards {
+                        match card {
+                            // Black ink would vanish on a dark panel, so it's left to the default text color
+                            CardView::FaceUp(c) if self.card_theme.suit_color(c.suit) != egui::Color32::BLACK => {
+                                ui.colored_label(self.card_theme.suit_color(c.suit), card.label());
+                            }
+                            _ => {
+                                ui.label(card.label());
+                            }
+                        }
+                    }
+                });
+            });
+        });
+    }
+
+    fn render_hand_fan(ui: &mut egui::Ui, cards: &[CardView], theme: CardTheme) {
+        const CARD_SIZE: egui::Vec2 = egui::Vec2::new(40.0, 56.0);
+        const STEP: f32 = 22.0; // Enough of each covered card to read its corner
+        const MAX_WIDTH: f32 = 240.0; // Long hands overlap more rather than grow
+
+        let step = match cards.len() {
+            0 | 1 => 0.0,
+            n => STEP.min((MAX_WIDTH - CARD_SIZE.x) / (n - 1) as f32),
+        };
+        let width = CARD_SIZE.x + step * cards.len().saturating_sub(1) as f32;
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(width, CARD_SIZE.y), egui::Sense::hover());
+        for (i, card) in cards.iter().enumerate() {
+            // Later cards are painted over earlier ones, so the last card is the one fully shown
+            let min = egui::pos2(rect.left() + step * i as f32, rect.top());
+            let face = egui::Rect::from_min_size(min, CARD_SIZE);
+            let (fill, text_color) = match card {
+                CardView::FaceUp(c) => (theme.face_fill(), theme.suit_color(c.suit)),
+                CardView::FaceDown => (egui::Color32::from_rgb(40, 70, 160), egui::Color32::WHITE), // Same back as the shoe
+            };
+            ui.painter().rect_filled(face, 3.0, fill);
+            ui.painter().rect_stroke(face, 3.0, (1.0, egui::Color32::GRAY));
+            ui.painter().text(min + egui::vec2(3.0, 2.0), egui::Align2::LEFT_TOP, card.label(), egui::TextStyle::Body, text_color);
+        }
+    }
+
+    fn chip_progress(&self, index: usize) -> Option<f32> {
+        const CHIP_SLIDE: f32 = 0.6; // Seconds for one hand's chips to reach their side
+        const STAGGER: f32 = 0.2; // Hands settle one after another
+
+        let started = self.chip_animation?;
+        let elapsed = started.elapsed().as_secs_f32() - STAGGER * index as f32;
+        Some((elapsed / CHIP_SLIDE).clamp(0.0, 1.0))
+    }
+
+    fn chip_slide(ui: &mut egui::Ui, outcome: Outcome, progress: f32) {
+        const SLIDE_SIZE: egui::Vec2 = egui::Vec2::new(160.0, 16.0);
+
+        let (rect, _) = ui.allocate_exact_size(SLIDE_SIZE, egui::Sense::hover());
+        let painter = ui.painter();
+        painter.text(rect.left_center(), egui::Align2::LEFT_CENTER, "Dealer", egui::TextStyle::Small, egui::Color32::GRAY);
+        painter.text(rect.right_center(), egui::Align2::RIGHT_CENTER, "You", egui::TextStyle::Small, egui::Color32::GRAY);
+        let start = rect.center().x; // The bet sits between the two
+        let end = match outcome {
+            Outcome::Won | Outcome::Blackjack => rect.right() - 30.0,
+            Outcome::Lost | Outcome::Busted => rect.left() + 40.0,
+            Outcome::Push | Outcome::BlackjackPush => start,
+        };
+        let chip = egui::pos2(start + (end - start) * progress, rect.center().y);
+        painter.circle_filled(chip, 6.0, egui::Color32::RED);
+        painter.circle_stroke(chip, 6.0, (1.0, egui::Color32::WHITE));
+    }
+
+    fn outcome_frame(&self, ui: &mut egui::Ui, index: usize, outcome: Outcome, heading: egui::Label) {
+        let bet = self.player_bets[index];
+        egui::Frame::group(ui.style())
+            .stroke(egui::Stroke::new(2.0, outcome.color()))
+            .show(ui, |ui| {
+                self.hand_row(ui, heading, &CardView::all_face_up(&self.player_hands[index]), ("player", index));
+                if let Some(progress) = self.chip_progress(index).filter(|&progress| progress < 1.0) {
+                    Self::chip_slide(ui, outcome, progress);
+                }
+                ui.horizontal(|ui| {
+                    ui.colored_label(outcome.color(), outcome.label()); // The result is spelled out, not only colored
+                    ui.label(format!(
+                        "Bet {}, net {}",
+                        self.format_money(bet),
+                        self.format_net(Self::hand_net(outcome, bet).saturating_add(self.bonus_winnings.get(index).copied().unwrap_or(0) as i64))
+                    ));
+                });
+            });
+    }
+
+    fn flash_overlay(&mut self, ctx: &egui::CtxRef) {
+        const FLASH_SECONDS: f32 = 0.8;
+
+        if let Some((kind, started)) = self.flash {
+            let elapsed = started.elapsed().as_secs_f32();
+            if elapsed >= FLASH_SECONDS {
+                self.flash = None;
+                return;
+            }
+
+            let fade = 1.0 - elapsed / FLASH_SECONDS;
+            let (color, text) = match kind {
+                FlashKind::Bust => (egui::Color32::RED, "Bust!"),
+                FlashKind::Blackjack => (egui::Color32::GOLD, "Blackjack!"),
+            };
+            let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("flash")));
+            let screen = ctx.input().screen_rect();
+            painter.rect_filled(screen, 0.0, color.linear_multiply(0.15 * fade)); // Light tint, not a strobe
+            painter.text(screen.center(), egui::Align2::CENTER_CENTER, text, egui::TextStyle::Heading, color.linear_multiply(fade));
+            ctx.request_repaint();
+        }
+    }
+
+    fn action_buttons(&mut self, ui: &mut egui::Ui) {
+        let hand_value = self.current_hand_value();
+        let (can_hit, can_stand) = match self.rules.beginner_guard {
+            Some(_) if self.is_card_hidden(self.current_hand, 0) => (true, true), // The guard would hint at the total
+            Some(guard) => (hand_value < guard.stand_at, hand_value >= guard.hit_under),
+            None => (true, true),
+        };
+        let at_21 = hand_value == 21 && !self.is_card_hidden(self.current_hand, 0) && self.should_show_player_total(); // A 21 waits for Stand unless auto-stand is on
+        let hit_button = if at_21 { egui::Button::new("Hit").text_color(egui::Color32::GRAY) } else { egui::Button::new("Hit") };
+        let hit = ui.add_enabled(can_hit, hit_button).on_disabled_hover_text("Stand on this total");
+        let hit = if at_21 { hit.on_hover_text("You have 21, stand instead") } else { hit };
+        if hit.clicked() {
+            self.last_error = self.hit().err();
+        }
+        if ui.add_enabled(can_stand, egui::Button::new("Stand")).on_disabled_hover_text("Always hit on this total").clicked() {
+            self.last_error = self.stand().err();
+        }
+        let legal_actions = self.legal_actions();
+        let stake = self.player_bets[self.current_hand];
+        if self.rules.double_for_less && stake > 1 {
+            ui.add(egui::Slider::new(&mut self.double_amount, 1..=stake).text("Double for"));
+            self.double_amount = self.rules.snap_to_chips(self.double_amount).min(stake); // Whole chips only
+        }
+        let funds_reason = format!(
+            "Needs another {}, you have {}",
+            self.format_money(stake),
+            self.format_money(self.total_money)
+        );
+        let double_reason = if !can_hit {
+            String::from("Stand on this total")
+        } else if !self.rules.double_after_split && self.split_hands[self.current_hand] {
+            String::from("No doubling after a split at this table")
+        } else if !self.double_allowed_on_total() {
+            String::from("This table only allows doubling on certain hard totals")
+        } else {
+            format!(
+                "Needs another {}, you have {}",
+                self.format_money(self.double_stake()),
+                self.format_money(self.total_money)
+            )
+        };
+        if ui.add_enabled(can_hit && legal_actions.contains(&Action::DoubleDown), egui::Button::new("Double Down")).on_disabled_hover_text(double_reason).clicked() {
+            self.last_error = self.double_down().err();
+        }
+        if Self::can_split(&self.player_hands[self.current_hand]) && !self.is_card_hidden(self.current_hand, 0) {
+            let reason = if !self.split_rank_allowed() {
+                String::from("This table doesn't allow splitting tens")
+            } else if self.player_hands.len() > self.rules.max_splits {
+                format!("No more than {} splits per round", self.rules.max_splits)
+            } else if !self.within_split_caps() {
+                format!("Aces can only be split {} time(s)", self.rules.max_ace_splits)
+            } else {
+                funds_reason
+            };
+            if ui.add_enabled(legal_actions.contains(&Action::Split), egui::Button::new("Split")).on_disabled_hover_text(reason).clicked() {
+                self.last_error = self.split().err();
+            }
+        }
+        let mut preview = format!("At risk: {}", self.format_money(self.total_at_risk()));
+        if legal_actions.contains(&Action::DoubleDown) {
+            let after = self.total_money - self.double_stake();
+            preview.push_str(&format!(", bankroll after a double: {}", self.format_money(after)));
+        }
+        if legal_actions.contains(&Action::Split) {
+            let after = self.total_money - stake;
+            preview.push_str(&format!(", after a split: {}", self.format_money(after)));
+        }
+        ui.label(preview);
+        if self.confirm_abandon {
+            ui.horizontal(|ui| {
+                ui.label("Give up and lose every stake this round?");
+                if ui.button("Yes, give up").clicked() {
+                    self.abandon_round();
+                }
+                if ui.button("Keep playing").clicked() {
+                    self.confirm_abandon = false;
+                }
+            });
+        } else if ui.button("Give Up").on_hover_text("Forfeit the whole round").clicked() {
+            self.confirm_abandon = true; // Asked again before anything is lost
+        }
+    }
+
+    fn deal_log_overlay(&mut self, ctx: &egui::CtxRef) {
+        let mut open = self.show_deal_log;
+        egui::Window::new("Deal Log").open(&mut open).show(ctx, |ui| {
+            for (number, (target, card)) in self.deal_log().iter().enumerate() {
+                let destination = match target {
+                    DealTarget::Player(index) => format!("hand {}", index + 1),
+                    DealTarget::Dealer => String::from("dealer"),
+                };
+                ui.monospace(format!("{:>2}. {:<3} to {}", number + 1, card.short(), destination));
+            }
+            ui.separator();
+            let next = self.peek_next(5).iter().rev().map(|c| c.short()).collect::<Vec<_>>(); // Next draw first
+            ui.monospace(format!("Next: {}", next.join(" ")));
+        });
+        self.show_deal_log = open;
+    }
+
+    fn chip_stack(ui: &mut egui::Ui, amount: usize) {
+        const CHIPS: [(usize, egui::Color32); 4] = [
+            (100, egui::Color32::BLACK),
+            (25, egui::Color32::GREEN),
+            (5, egui::Color32::RED),
+            (1, egui::Color32::WHITE),
+        ];
+        const MAX_CHIPS: usize = 20; // Big bets just max out the stack
+        const CHIP_SIZE: egui::Vec2 = egui::Vec2::new(36.0, 5.0);
+
+        let mut chips = Vec::new();
+        let mut remaining = amount;
+        for &(denomination, color) in CHIPS.iter() {
+            while remaining >= denomination && chips.len() < MAX_CHIPS {
+                chips.push(color);
+                remaining -= denomination;
+            }
+        }
+
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(CHIP_SIZE.x, CHIP_SIZE.y * MAX_CHIPS as f32), egui::Sense::hover());
+        for (i, color) in chips.into_iter().enumerate() {
+            let bottom = rect.bottom() - CHIP_SIZE.y * i as f32;
+            let chip = egui::Rect::from_min_max(egui::pos2(rect.left(), bottom - CHIP_SIZE.y), egui::pos2(rect.right(), bottom));
+            ui.painter().rect_filled(chip, 2.0, color);
+            ui.painter().rect_stroke(chip, 2.0, (1.0, egui::Color32::GRAY));
+        }
+    }
+
+    fn shoe_stack(ui: &mut egui::Ui, remaining: usize, shoe_size: usize) {
+        const SHOE_SIZE: egui::Vec2 = egui::Vec2::new(28.0, 100.0); // Same height as a full chip stack
+
+        let (rect, response) = ui.allocate_exact_size(SHOE_SIZE, egui::Sense::hover());
+        let fraction = if shoe_size == 0 { 0.0 } else { remaining as f32 / shoe_size as f32 };
+        let top = rect.bottom() - rect.height() * fraction;
+        let cards = egui::Rect::from_min_max(egui::pos2(rect.left(), top), rect.right_bottom());
+        ui.painter().rect_filled(cards, 2.0, egui::Color32::from_rgb(40, 70, 160)); // Card backs
+        ui.painter().rect_stroke(rect, 2.0, (1.0, egui::Color32::GRAY));
+        response.on_hover_text(format!("{} cards left in the shoe", remaining));
+    }
+
+    fn quiz_ui(&mut self, ui: &mut egui::Ui) {
+        if self.quiz.hand.is_empty() {
+            self.quiz.next_hand(&mut thread_rng());
+        }
+        self.hand_row(ui, egui::Label::new("Total?"), &CardView::all_face_up(&self.quiz.hand), "quiz");
+        let mut check = false;
+        ui.horizontal(|ui| {
+            let response = ui.text_edit_singleline(&mut self.quiz.answer);
+            check = response.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+            check |= ui.button("Check").clicked();
+        });
+        if check && self.quiz.grade(self.rules.value_rules).is_some() {
+            self.quiz.next_hand(&mut thread_rng());
+        }
+        match self.quiz.last_result {
+            Some((true, total)) => {
+                ui.colored_label(egui::Color32::GREEN, format!("Right, {}", total));
+            }
+            Some((false, total)) => {
+                ui.colored_label(egui::Color32::RED, format!("No, that hand was {}", total));
+            }
+            None => {}
+        }
+        if self.quiz.asked > 0 {
+            ui.label(format!("{} of {} right ({:.0}%)", self.quiz.correct, self.quiz.asked, self.quiz.accuracy() * 100.0));
+        }
+    }
+
+    fn history_ui(&self, ui: &mut egui::Ui) {
+        if self.round_history.is_empty() {
+            ui.label("No rounds played yet.");
+            return;
+        }
+
+        egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+            for (number, record) in self.round_history.iter().enumerate().rev() {
+                let bet: usize = record.bets.iter().sum();
+                let mut title = format!(
+                    "Round {}: bet {}, net {}",
+                    number + 1,
+                    self.format_money(bet),
+                    self.format_net(record.net)
+                );
+                if record.bad_beat {
+                    title.push_str(" - bad beat!");
+                }
+                egui::CollapsingHeader::new(title).id_source(("round", number)).show(ui, |ui| {
+                    ui.label(format!("Dealer: {}", hand_to_string(&record.dealer_hand, self.rules.value_rules)));
+                    for (index, hand) in record.player_hands.iter().enumerate() {
+                        ui.label(format!(
+                            "Hand {}: {} {} Bet: {}",
+                            index + 1,
+                            hand_to_string(hand, self.rules.value_rules),
+                            record.outcomes[index].label(),
+                            self.format_money(record.bets[index])
+                        ));
+                    }
+                    let actions = record
+                        .actions
+                        .iter()
+                        .map(|(index, action)| format!("Hand {} {:?}", index + 1, action))
+                        .collect::<Vec<_>>();
+                    ui.label(format!("Actions: {}", if actions.is_empty() { String::from("none") } else { actions.join(", ") }));
+                });
+            }
+        });
+        if ui.button("Copy as CSV").on_hover_text("Paste into a spreadsheet").clicked() {
+            ui.output().copied_text = self.stats_to_csv();
+        }
+    }
+
+    fn settings_ui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.rules.max_splits, 0..=3).text("Max splits"));
+        ui.add(egui::Slider::new(&mut self.rules.max_ace_splits, 0..=3).text("Max ace splits"));
+
+        ui.horizontal(|ui| {
+            ui.label("When the deck runs out:");
+            ui.radio_value(&mut self.rules.low_deck_policy, LowDeckPolicy::Reshuffle, "Reshuffle");
+            ui.radio_value(&mut self.rules.low_deck_policy, LowDeckPolicy::VoidRound, "Void round");
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Dealer plays:");
+            ui.radio_value(&mut self.rules.dealer_play, DealerPlay::Standard, "Standard");
+            ui.radio_value(&mut self.rules.dealer_play, DealerPlay::BeatBestHand, "Beat my best hand");
+        });
+        if self.rules.dealer_play == DealerPlay::Standard {
+            ui.add(egui::Slider::new(&mut self.rules.dealer_threshold.stand_hard, 12..=21).text("Dealer stands on hard"));
+            ui.add(egui::Slider::new(&mut self.rules.dealer_threshold.stand_soft, 12..=21).text("Dealer stands on soft"));
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Hole card:");
+            ui.radio_value(&mut self.rules.hole_card, HoleCard::American, "American");
+            ui.radio_value(&mut self.rules.hole_card, HoleCard::EuropeanNHC, "European (no hole card)");
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Double down on:");
+            ui.radio_value(&mut self.rules.double_restriction, DoubleRule::Any, "Any two cards");
+            ui.radio_value(&mut self.rules.double_restriction, DoubleRule::NineTenEleven, "Hard 9-11");
+            ui.radio_value(&mut self.rules.double_restriction, DoubleRule::TenEleven, "Hard 10-11");
+        });
+        ui.checkbox(&mut self.rules.double_after_split, "Double after split");
+        ui.checkbox(&mut self.rules.double_for_less, "Allow doubling for less");
+        ui.checkbox(&mut self.rules.split_aces_locked, "Split aces get one card only");
+        ui.checkbox(&mut self.rules.allow_split_tens, "Allow splitting tens");
+
+        ui.horizontal(|ui| {
+            ui.label("Shuffle:");
+            let kind = &mut self.rules.shuffle_kind;
+            if ui.radio(*kind == ShuffleKind::Perfect, "Perfect").clicked() {
+                *kind = ShuffleKind::Perfect;
+            }
+            if ui.radio(matches!(kind, ShuffleKind::Riffle { .. }), "Riffle").clicked() {
+                *kind = ShuffleKind::Riffle { passes: 7 };
+            }
+            if ui.radio(matches!(kind, ShuffleKind::Overhand { .. }), "Overhand").clicked() {
+                *kind = ShuffleKind::Overhand { passes: 4 };
+            }
+        });
+        if let ShuffleKind::Riffle { passes } | ShuffleKind::Overhand { passes } = &mut self.rules.shuffle_kind {
+            ui.add(egui::Slider::new(passes, 1..=20).text("Shuffle passes"));
+        }
+
+        ui.add(egui::Slider::new(&mut self.rules.chip_denomination, 1..=25).text("Chip denomination"));
+        ui.add(egui::Slider::new(&mut self.rules.burn_cards, 0..=5).text("Burn cards after shuffle"));
+        if ui.add(egui::Slider::new(&mut self.rules.num_decks, 1..=8).text("Decks in the shoe")).changed() {
+            self.needs_reshuffle = true; // The new size takes effect with the next deal
+        }
+        ui.add(egui::Slider::new(&mut self.rules.penetration, 0.5..=0.9).text("Reshuffle after this much is dealt"));
+        let mut round_limit = self.rules.max_rounds_per_shoe.is_some();
+        if ui.checkbox(&mut round_limit, "Also reshuffle after a number of rounds").changed() {
+            self.rules.max_rounds_per_shoe = if round_limit { Some(10) } else { None };
+        }
+        if let Some(rounds) = &mut self.rules.max_rounds_per_shoe {
+            ui.add(egui::Slider::new(rounds, 1..=50).text("Rounds per shoe"));
+        }
+        ui.checkbox(&mut self.rules.csm, "Continuous shuffler: every round comes from a full shoe");
+
+        ui.checkbox(&mut self.rules.dealer_peek, "Dealer peeks for blackjack");
+        for (kind, label) in [(BonusKind::SuitedBlackjack, "Bonus: suited blackjack pays 2:1"), (BonusKind::SuitedSevens, "Bonus: suited 7-7-7 pays 3:1")] {
+            let mut enabled = self.rules.bonuses.contains(&kind);
+            if ui.checkbox(&mut enabled, label).changed() {
+                self.rules.bonuses.retain(|&k| k != kind);
+                if enabled {
+                    self.rules.bonuses.push(kind);
+                }
+            }
+        }
+        let mut charlie = self.rules.charlie.is_some();
+        if ui.checkbox(&mut charlie, "Charlie rule").changed() {
+            self.rules.charlie = if charlie { Some(5) } else { None };
+        }
+        if let Some(cards) = &mut self.rules.charlie {
+            ui.add(egui::Slider::new(cards, 5..=7).text("Cards for a Charlie"));
+        }
+        ui.checkbox(&mut self.rules.dealer_22_pushes, "Dealer 22 pushes");
+        ui.checkbox(&mut self.rules.forgiving_first_hit, "Forgiving: first hit can't bust");
+        ui.checkbox(&mut self.rules.blind_first_card, "Blind variant: first card face down");
+
+        ui.checkbox(&mut self.rules.value_rules.aces_low_only, "Hard mode: aces only count as 1");
+
+        let mut guided = self.rules.beginner_guard.is_some();
+        if ui.checkbox(&mut guided, "Beginner guard").changed() {
+            self.rules.beginner_guard = if guided { Some(BeginnerGuard::default()) } else { None };
+        }
+        if let Some(guard) = &mut self.rules.beginner_guard {
+            ui.add(egui::Slider::new(&mut guard.hit_under, 4..=21).text("Always hit under"));
+            ui.add(egui::Slider::new(&mut guard.stand_at, 4..=21).text("Always stand at"));
+        }
+
+        let practice_upcards = [
+            Value::Number(2), Value::Number(3), Value::Number(4), Value::Number(5), Value::Number(6),
+            Value::Number(7), Value::Number(8), Value::Number(9), Value::Number(10), Value::Ace,
+        ];
+        let mut forced_upcard = self.forced_upcard;
+        egui::ComboBox::from_label("Practice vs dealer upcard")
+            .selected_text(forced_upcard.map_or(String::from("Random"), |value| value.rank_str()))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut forced_upcard, None, "Random");
+                for value in practice_upcards.iter() {
+                    ui.selectable_value(&mut forced_upcard, Some(*value), value.rank_str());
+                }
+            });
+        self.force_dealer_upcard(forced_upcard);
+
+        ui.horizontal(|ui| {
+            ui.label("Goal:");
+            let goal = &mut self.win_condition;
+            if ui.radio(*goal == WinCondition::Endless, "Free play").clicked() {
+                *goal = WinCondition::Endless;
+            }
+            if ui.radio(matches!(goal, WinCondition::TargetBankroll(_)), "Reach a bankroll").clicked() {
+                *goal = WinCondition::TargetBankroll(200);
+            }
+            if ui.radio(matches!(goal, WinCondition::SurviveRounds(_)), "Survive rounds").clicked() {
+                *goal = WinCondition::SurviveRounds(20);
+            }
+            if ui.radio(matches!(goal, WinCondition::BankrollFloor(_)), "Stay above").clicked() {
+                *goal = WinCondition::BankrollFloor(50);
+            }
+        });
+        match &mut self.win_condition {
+            WinCondition::Endless => {}
+            WinCondition::TargetBankroll(amount) => {
+                ui.add(egui::Slider::new(amount, 110..=1000).text("Target bankroll"));
+            }
+            WinCondition::SurviveRounds(rounds) => {
+                ui.add(egui::Slider::new(rounds, 5..=100).text("Rounds to survive"));
+            }
+            WinCondition::BankrollFloor(amount) => {
+                ui.add(egui::Slider::new(amount, 0..=90).text("Bankroll floor"));
+            }
+        }
+
+        let mut topup = self.auto_topup.is_some();
+        if ui.checkbox(&mut topup, "Practice: top up the bankroll instead of going broke").changed() {
+            self.auto_topup = if topup { Some(100) } else { None };
+        }
+        if let Some(amount) = &mut self.auto_topup {
+            ui.add(egui::Slider::new(amount, 10..=1000).text("Top up to"));
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Currency symbol:");
+            ui.add(egui::TextEdit::singleline(&mut self.money_format.symbol).desired_width(40.0));
+            ui.label("Thousands separator:");
+            for (separator, label) in [(',', ","), ('.', "."), (' ', "space"), ('\'', "'")] {
+                ui.radio_value(&mut self.money_format.separator, separator, label);
+            }
+        });
+        let mut capped = self.money_format.display_cap.is_some();
+        if ui.checkbox(&mut capped, "Cap the displayed bankroll").changed() {
+            self.money_format.display_cap = if capped { Some(1_000_000) } else { None };
+        }
+        if let Some(cap) = &mut self.money_format.display_cap {
+            ui.add(egui::Slider::new(cap, 1_000..=1_000_000_000).logarithmic(true).text("Show amounts above this as a cap"));
+        }
+
+        ui.checkbox(&mut self.animate_money, "Animate bankroll changes");
+        ui.checkbox(&mut self.animate_deal, "Animate the deal and payouts");
+        ui.add(egui::Slider::new(&mut self.advance_delay, 0.0..=2.0).text("Pause between split hands (s)"));
+        if ui.checkbox(&mut self.flash_effects, "Flash on bust and blackjack").changed() {
+            self.flash = None;
+        }
+        ui.checkbox(&mut self.reveal_hole_card, "Show dealer's hole card (teaching)");
+        ui.checkbox(&mut self.fan_cards, "Show cards as a fan");
+        ui.horizontal(|ui| {
+            ui.label("Card colors:");
+            ui.radio_value(&mut self.card_theme, CardTheme::Classic, "Classic");
+            ui.radio_value(&mut self.card_theme, CardTheme::FourColor, "Four-color");
+            ui.radio_value(&mut self.card_theme, CardTheme::Minimal, "Minimal");
+        });
+        ui.checkbox(&mut self.show_player_total, "Show my total while playing");
+        ui.horizontal(|ui| {
+            ui.label("Cards left in the shoe:");
+            ui.radio_value(&mut self.shoe_count_display, ShoeCountDisplay::Always, "Always");
+            ui.radio_value(&mut self.shoe_count_display, ShoeCountDisplay::BetweenRounds, "Between rounds");
+            ui.radio_value(&mut self.shoe_count_display, ShoeCountDisplay::AfterReshuffle, "After a reshuffle");
+        });
+        ui.checkbox(&mut self.auto_stand_21, "Stand automatically on 21");
+        ui.checkbox(&mut self.auto_split_aces_eights, "Beginner: always split aces and eights");
+        ui.add(egui::Slider::new(&mut self.low_shoe_warning_threshold, 0..=30).text("Warn this many cards before the cut card"));
+        ui.checkbox(&mut self.narrate_dealer, "Narrate the dealer's decisions");
+        ui.checkbox(&mut self.training_mode, "Training: show what's left in the shoe");
+        ui.checkbox(&mut self.pit_boss, "Training: warn when bets follow the count");
+        ui.checkbox(&mut self.show_deal_log, "Debug: show deal log");
+
+        ui.horizontal(|ui| {
+            if ui.button("Save Settings").clicked() {
+                if let Err(err) = self.rules.save() {
+                    eprintln!("Failed to save settings: {}", err);
+                }
+            }
+            if ui.button("Copy Session").on_hover_text("Seed, rules and round log as JSON").clicked() {
+                ui.output().copied_text = self.export_session();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.session_paste).hint_text("Paste a copied session").desired_width(200.0));
+            let valid = serde_json::from_str::<SessionExport>(&self.session_paste).is_ok();
+            let load = ui.add_enabled(valid, egui::Button::new("Load Session")).on_disabled_hover_text("Paste the JSON from Copy Session");
+            if load.clicked() {
+                let json = self.session_paste.clone();
+                self.load_session(&json);
+            }
+        });
+    }
+}
+
+impl epi::App for BlackjackApp {
+    fn name(&self) -> &str {
+        "Blackjack"
+    }
+
+    fn on_exit(&mut self) {
+        if matches!(self.game_state, GameState::Startup) {
+            return; // Leaving from the prompt keeps the saved game as it was
+        }
+        if let Err(err) = self.save_state(&*platform_storage()) {
+            eprintln!("Failed to save game: {}", err);
+        }
+    }
+
+    fn update(&mut self, ctx: &egui::CtxRef, _frame: &mut epi::Frame) {
+        // Taken before any widget runs: a text field that submits on Enter has already let go of focus by the time
+        // the betting controls are drawn, and its Enter must not also deal
+        let typing = ctx.wants_keyboard_input();
+        let settled = matches!(self.game_state, GameState::GameOver(_));
+        if settled && !self.settled_round_saved {
+            // Save after every settled round, since a browser tab may close without an exit hook
+            if let Err(err) = self.save_state(&*platform_storage()) {
+                eprintln!("Failed to save game: {}", err);
+            }
+        }
+        self.settled_round_saved = settled;
+
+        let target_money = self.total_money as f32;
+        if self.animate_money && (target_money - self.displayed_money).abs() > 0.5 {
+            let dt = ctx.input().unstable_dt.min(1.0 / 30.0);
+            self.displayed_money += (target_money - self.displayed_money) * (dt * 8.0).min(1.0);
+            ctx.request_repaint(); // Keep easing until the readout catches up
+        } else {
+            self.displayed_money = target_money;
+        }
+
+        if let Some((shown, since)) = self.deal_animation {
+            const DEAL_INTERVAL: f32 = 0.25; // Seconds between opening cards
+            if shown >= self.opening_deal().len() {
+                self.deal_animation = None;
+            } else {
+                if since.elapsed().as_secs_f32() >= DEAL_INTERVAL {
+                    self.deal_animation = Some((shown + 1, Instant::now()));
+                }
+                ctx.request_repaint();
+            }
+        }
+
+        if self.chip_animation.is_some() {
+            if (0..self.outcomes.len()).all(|index| self.chip_progress(index) == Some(1.0)) {
+                self.chip_animation = None;
+            } else {
+                ctx.request_repaint();
+            }
+        }
+
+        if let Some(started) = self.pending_advance {
+            if started.elapsed().as_secs_f32() >= self.advance_delay {
+                self.flush_pending_advance();
+            } else {
+                ctx.request_repaint(); // Wake up when the pause is over
+            }
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Blackjack");
+            ui.label(format!("Bankroll: {}", self.format_money(self.displayed_money.round() as usize)));
+            if self.rounds_played > 0 {
+                ui.label(format!(
+                    "Best hand: {}, Worst: {}",
+                    self.format_net(self.best_round()),
+                    self.format_net(self.worst_round())
+                ));
+                let minutes = self.session_start.elapsed().as_secs() / 60;
+                ui.label(format!("Session: {} min, {:.0} hands/hour", minutes, self.hands_per_hour()));
+            }
+            let earlier_shoes = self.shoe_results[..self.shoe_results.len().saturating_sub(1)]
+                .iter()
+                .map(|&net| self.format_net(net))
+                .collect::<Vec<_>>();
+            let shoe_label = ui.label(format!("This shoe: {}", self.format_net(self.current_shoe_net())));
+            if !earlier_shoes.is_empty() {
+                shoe_label.on_hover_text(format!("Earlier shoes: {}", earlier_shoes.join(", ")));
+            }
+            if let Some(err) = &self.last_error {
+                ui.colored_label(egui::Color32::RED, err.to_string());
+            }
+            if let Some(note) = &self.assist_note {
+                ui.colored_label(egui::Color32::LIGHT_BLUE, note);
+            }
+            if self.should_show_shoe_count() {
+                let penetration = self.shoe_penetration();
+                let mut shoe_text = format!("Shoe {:.0}% dealt, {} cards left", penetration * 100.0, self.cards_remaining());
+                if !self.burned.is_empty() {
+                    shoe_text.push_str(&format!(", {} burned", self.burned.len()));
+                }
+                let bar = ui.add(egui::ProgressBar::new(penetration).text(shoe_text)).rect;
+                if let Some(cut) = self.cut_card_mark() {
+                    // This egui's painter has no vline, so the tick is a vertical segment
+                    let x = bar.left() + bar.width() * cut;
+                    ui.painter().line_segment([egui::pos2(x, bar.top()), egui::pos2(x, bar.bottom())], (2.0, egui::Color32::RED));
+                }
+                if self.low_shoe_warning() {
+                    ui.colored_label(egui::Color32::YELLOW, format!("{} cards to the cut card, reshuffle coming", self.cards_to_cut()));
+                }
+            }
+            if self.pit_boss && self.heat_level() > 0.7 {
+                ui.colored_label(egui::Color32::from_rgb(255, 140, 0), "The pit boss is watching your bets");
+            }
+            if self.training_mode && !self.rules.csm {
+                let composition = self.remaining_composition();
+                ui.label(format!(
+                    "Left in shoe: {:.0}% tens and aces, {:.0}% 7-9, {:.0}% 2-6",
+                    composition.share(composition.high) * 100.0,
+                    composition.share(composition.neutral) * 100.0,
+                    composition.share(composition.low) * 100.0
+                ));
+            }
+            match self.game_state {
+                GameState::Startup => {
+                    ui.label("A saved game was found.");
+                    ui.horizontal(|ui| {
+                        if ui.button("Continue").clicked() {
+                            self.continue_session(&*platform_storage());
+                        }
+                        if ui.button("New Session").clicked() {
+                            self.new_game();
+                        }
+                    });
+                },
+                GameState::Betting => {
+                    if let Some(summary) = self.previous_round_summary() {
+                        ui.add(egui::Label::new(summary).weak());
+                    }
+                    // Whole chips, from one chip up to everything in the bankroll
+                    let chip = self.rules.chip_denomination.max(1);
+                    let most = (self.total_money / chip * chip).max(chip);
+                    self.wager = self.wager.clamp(chip, most);
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(self.wager > chip, egui::Button::new("-")).clicked() {
+                            self.wager = self.rules.snap_to_chips(self.wager - chip);
+                        }
+                        ui.label(format!("Bet: {}", self.format_money(self.wager)));
+                        if ui.add_enabled(self.wager + chip <= most, egui::Button::new("+")).clicked() {
+                            self.wager = self.rules.snap_to_chips(self.wager + chip);
+                        }
+                    });
+                    if most > chip {
+                        ui.add(egui::Slider::new(&mut self.wager, chip..=most).text("Bet"));
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Type a bet:");
+                        let entry = ui.add(egui::TextEdit::singleline(&mut self.bet_text).desired_width(60.0)).on_hover_text("Up and Down change it by one chip");
+                        if entry.has_focus() || !ui.ctx().wants_keyboard_input() {
+                            // Other text fields keep their arrow keys
+                            if ui.input().key_pressed(egui::Key::ArrowUp) && self.wager + chip <= most {
+                                self.wager += chip;
+                                self.bet_text = self.wager.to_string();
+                            }
+                            if ui.input().key_pressed(egui::Key::ArrowDown) && self.wager > chip {
+                                self.wager = self.rules.snap_to_chips(self.wager - chip);
+                                self.bet_text = self.wager.to_string();
+                            }
+                        }
+                        if entry.has_focus() {
+                            match self.parse_bet(&self.bet_text) {
+                                Ok(amount) => self.wager = amount,
+                                Err(problem) => {
+                                    ui.colored_label(egui::Color32::RED, problem);
+                                }
+                            }
+                        } else {
+                            self.bet_text = self.wager.to_string(); // Follows the buttons and slider
+                        }
+                    });
+                    if self.training_mode && !self.rules.csm {
+                        let suggestion = Self::kelly_bet(self.total_money, self.count_edge(), chip, most);
+                        ui.horizontal(|ui| {
+                            ui.label(format!("Kelly suggestion at this count: {}", self.format_money(suggestion)))
+                                .on_hover_text("A fraction of your bankroll sized by the count edge. Only a suggestion");
+                            if ui.button("Use").clicked() {
+                                self.wager = suggestion;
+                            }
+                        });
+                    }
+                    if !self.recent_bets.is_empty() {
+                        ui.horizontal(|ui| {
+                            ui.label("Recent bets:");
+                            for amount in self.recent_bets.clone().into_iter().rev() {
+                                let affordable = amount <= self.total_money;
+                                let preset = ui.add_enabled(affordable, egui::Button::new(self.format_money(amount)));
+                                if preset.on_disabled_hover_text("More than your bankroll").clicked() {
+                                    self.wager = amount;
+                                }
+                            }
+                        });
+                    }
+                    let affordable = self.rules.snap_to_chips(self.wager) <= self.total_money;
+                    let start = ui
+                        .add_enabled(affordable, egui::Button::new("Place Bet and Start"))
+                        .on_hover_text("Enter")
+                        .on_disabled_hover_text("Not enough left for a bet, start a new game");
+                    if affordable && ui.memory().focus().is_none() {
+                        start.request_focus(); // Keyboard users land on the main action
+                    }
+                    // One check covers both, so a focused button activated with Enter can't deal twice
+                    let enter = ui.input().key_pressed(egui::Key::Enter) && (start.has_focus() || !typing);
+                    if start.clicked() || (affordable && enter) {
+                        self.last_error = self.place_bet().err();
+                    }
+                },
+                GameState::PlayerTurn => {
+                    // Display only: the dealer's play never depends on this flag
+                    let (dealer_total, mut dealer_cards) = if self.reveal_hole_card {
+                        (Self::total_label(&self.dealer_hand, self.rules.value_rules), CardView::all_face_up(&self.dealer_hand))
+                    } else {
+                        let mut cards = vec![CardView::FaceUp(&self.dealer_hand[0])];
+                        if self.dealer_hand.len() > 1 {
+                            cards.push(CardView::FaceDown); // No-hole-card tables have nothing here yet
+                        }
+                        (format!("{} showing", self.dealer_value()), cards)
+                    };
+                    let dealing = self.deal_animation.is_some();
+                    let dealer_total = if dealing { String::from("...") } else { dealer_total };
+                    dealer_cards.truncate(self.visible_cards(DealTarget::Dealer)); // The hole card arrives face down
+                    self.hand_row(ui, egui::Label::new(format!("Dealer: {}", dealer_total)), &dealer_cards, "dealer");
+                    for index in 0..self.player_hands.len() {
+                        let heading = format!(
+                            "{} Hand {}: {}, Bet: {}{}",
+                            if index == self.current_hand { "▶" } else { "  " },
+                            index + 1,
+                            if dealing { String::from("...") } else { self.player_total_label(index) },
+                            self.format_money(self.player_bets[index]),
+                            if self.is_hand_locked(index) { " (locked)" } else { "" }
+                        );
+                        let heading = egui::Label::new(heading);
+                        let heading = match (index == self.current_hand, self.pending_advance.is_some()) {
+                            (true, true) => heading.strong().background_color(egui::Color32::from_gray(64)), // Just finished
+                            (true, false) => heading.strong(),
+                            _ => heading,
+                        };
+                        let mut cards = self.player_card_views(index);
+                        cards.truncate(self.visible_cards(DealTarget::Player(index)));
+                        self.hand_row(ui, heading, &cards, ("player", index));
+                    }
+                    ui.horizontal(|ui| {
+                        Self::chip_stack(ui, self.player_bets[self.current_hand]);
+                        if self.should_show_shoe_count() {
+                            Self::shoe_stack(ui, self.cards_remaining(), self.shoe_size);
+                        }
+                    });
+                    if dealing {
+                        ui.label("Dealing...");
+                    } else {
+                        if self.training_mode && !self.is_card_hidden(self.current_hand, 0) && self.should_show_player_total() {
+                            let totals = self.reachable_totals();
+                            let bust: f64 = totals.iter().filter(|&&(total, _)| total > 21).map(|&(_, chance)| chance).sum();
+                            let safe = totals
+                                .iter()
+                                .filter(|&&(total, _)| total <= 21)
+                                .map(|&(total, chance)| format!("{} {:.0}%", total, chance * 100.0))
+                                .collect::<Vec<_>>();
+                            let mut readout = format!("One more card: {}, bust {:.0}%", safe.join(", "), bust * 100.0);
+                            if self.current_hand_is_soft() {
+                                readout.push_str(" (soft, so the ace can drop to 1)");
+                            }
+                            ui.label(readout);
+                        }
+                        if self.training_mode {
+                            let finishes = self.dealer_outcome_distribution(&self.dealer_hand[0]);
+                            let made = (17..=21).zip(finishes.iter()).map(|(total, chance)| format!("{} {:.0}%", total, chance * 100.0));
+                            ui.label(format!("Dealer finishes: {}, bust {:.0}%", made.collect::<Vec<_>>().join(", "), finishes[5] * 100.0));
+                        }
+                        ui.scope(|ui| {
+                            // Nothing can be played while the finished split hand is on show
+                            ui.set_enabled(self.pending_advance.is_none());
+                            self.action_buttons(ui);
+                        });
+                    }
+                },
+                GameState::DealerTurn => {
+                    ui.label("Dealer's turn...");
+                },
+                GameState::GameOver(ref message) => {
+                    let heading = egui::Label::new(format!("Dealer: {}", Self::total_label(&self.dealer_hand, self.rules.value_rules)));
+                    self.hand_row(ui, heading, &CardView::all_face_up(&self.dealer_hand), "dealer");
+                    if !self.round_abandoned {
+                        ui.label(self.dealer_play_summary());
+                    }
+                    if self.narrate_dealer {
+                        for line in &self.dealer_narration {
+                            ui.label(line);
+                        }
+                    }
+                    for (index, hand) in self.player_hands.iter().enumerate() {
+                        let heading = egui::Label::new(format!("Hand {}: {}", index + 1, Self::total_label(hand, self.rules.value_rules)));
+                        match self.outcomes.get(index) {
+                            Some(&outcome) => self.outcome_frame(ui, index, outcome, heading),
+                            None => self.hand_row(ui, heading, &CardView::all_face_up(hand), ("player", index)), // Voided rounds have no outcomes
+                        }
+                    }
+                    ui.label(message); // Plain-text summary of every hand
+                    if !self.outcomes.is_empty() {
+                        ui.collapsing("Why?", |ui| {
+                            for index in 0..self.outcomes.len() {
+                                ui.label(format!("Hand {}: {}", index + 1, self.explain_outcome(index)));
+                                if !matches!(self.outcomes[index], Outcome::Lost | Outcome::Busted) {
+                                    continue;
+                                }
+                                // What standing would have done instead of the hand's last draw
+                                let last_draw = self
+                                    .round_actions
+                                    .iter()
+                                    .rposition(|&(hand, action)| hand == index && matches!(action, Action::Hit | Action::DoubleDown));
+                                if let Some(outcome) = last_draw.and_then(|decision| self.counterfactual_stand(decision)) {
+                                    ui.label(format!("    Standing instead of your last card: {}", outcome.label()));
+                                }
+                            }
+                        });
+                    }
+                    match self.goal_result {
+                        Some(won) => {
+                            ui.heading(if won { "Goal reached!" } else { "Goal failed." });
+                            if ui.button("New Game").clicked() {
+                                self.new_game();
+                            }
+                        }
+                        None => {
+                            if ui.button("Play Again").clicked() {
+                                self.game_state = GameState::Betting; // Settle on the next bet before dealing
+                            }
+                        }
+                    }
+                },
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("New Game").clicked() {
+                    self.new_game();
+                }
+                ui.checkbox(&mut self.lock_seed, "Lock seed").on_hover_text("New Game deals the same cards again");
+                let between_rounds = !matches!(self.game_state, GameState::PlayerTurn);
+                if ui.add_enabled(between_rounds, egui::Button::new("Reshuffle")).on_disabled_hover_text("Finish the round first").clicked() {
+                    self.reshuffle_now();
+                }
+            });
+            ui.collapsing("Describe Table", |ui| ui.label(self.describe_state()));
+            ui.collapsing("Table Rules", |ui| {
+                for line in self.rules.describe() {
+                    ui.label(line);
+                }
+                ui.label(format!("Estimated house edge: {:.2}%", self.rules.estimated_edge()))
+                    .on_hover_text("Rough figure from published rule adjustments, assuming basic strategy");
+            });
+            ui.collapsing("Round History", |ui| self.history_ui(ui));
+            ui.collapsing("Practice: Hand Totals", |ui| self.quiz_ui(ui));
+            ui.collapsing("Settings", |ui| self.settings_ui(ui));
+            self.log_rule_changes();
+        });
+
+        self.flash_overlay(ctx);
+        self.deal_log_overlay(ctx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(value: Value, suit: Suit) -> Card {
+        Card { value, suit }
+    }
+
+    fn n(value: u8, suit: Suit) -> Card {
+        card(Value::Number(value), suit)
+    }
+
+    /// Deals one round from `cards` in casino order: player, upcard, player, hole card, then draws.
+    fn scripted(cards: Vec<Card>) -> BlackjackApp {
+        scripted_with(cards, RuleSet::default())
+    }
+
+    fn scripted_with(cards: Vec<Card>, rules: RuleSet) -> BlackjackApp {
+        let mut app = BlackjackApp::with_stacked_deck(cards, rules);
+        app.advance_delay = 0.0;
+        app
+    }
+
+    fn settled(app: &BlackjackApp) -> bool {
+        matches!(app.game_state, GameState::GameOver(_))
+    }
+
+    #[derive(Default)]
+    struct MemoryStorage(std::cell::RefCell<std::collections::HashMap<String, String>>);
+
+    impl Storage for MemoryStorage {
+        fn read(&self, key: &str) -> Option<String> {
+            self.0.borrow().get(key).cloned()
+        }
+
+        fn write(&self, key: &str, value: &str) -> io::Result<()> {
+            self.0.borrow_mut().insert(key.to_string(), value.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn money_is_grouped_in_thousands() {
+        let app = BlackjackApp::undealt(0, RuleSet::default());
+        assert_eq!(app.format_money(0), "$0");
+        assert_eq!(app.format_money(1000), "$1,000");
+        assert_eq!(app.format_money(1234567), "$1,234,567");
+        assert_eq!(app.format_net(-1000), "-$1,000");
+    }
+
+    #[test]
+    fn money_symbol_and_separator_are_configurable() {
+        let mut app = BlackjackApp::undealt(0, RuleSet::default());
+        app.money_format = MoneyFormat { symbol: String::from("€"), separator: '.', display_cap: Some(1_000_000) };
+        assert_eq!(app.format_money(1234), "€1.234");
+        assert_eq!(app.format_money(1234567), "€1.000.000+");
+    }
+
+    #[test]
+    fn seeded_shuffles_put_every_card_in_every_position_evenly() {
+        // Chi-square over the 52x52 table of card positions; a biased swap loop lands far above the bound
+        const SHUFFLES: usize = 5200;
+        let reference = BlackjackApp::create_deck(1);
+        let index_of = |card: &Card| reference.iter().position(|c| c.suit == card.suit && c.value == card.value).unwrap();
+        let mut counts = vec![[0usize; 52]; 52];
+        let mut app = BlackjackApp::undealt(0, RuleSet::default());
+        for seed in 0..SHUFFLES as u64 {
+            app.seed = seed;
+            app.shuffles = 0;
+            app.deck = BlackjackApp::create_deck(1);
+            app.shuffle_deck();
+            for (position, card) in app.deck.iter().enumerate() {
+                counts[index_of(card)][position] += 1;
+            }
+        }
+
+        let expected = SHUFFLES as f64 / 52.0;
+        let chi_square: f64 = counts.iter().flatten().map(|&observed| (observed as f64 - expected).powi(2) / expected).sum();
+        let freedom: f64 = 51.0 * 51.0;
+        assert!(chi_square < freedom + 5.0 * (2.0 * freedom).sqrt(), "chi-square {} is too high for a fair shuffle", chi_square);
+    }
+
+    #[test]
+    fn hand_values_count_one_ace_high_at_most() {
+        let rules = ValueRules::default();
+        let hand = [card(Value::Ace, Suit::Spades), card(Value::Ace, Suit::Hearts), n(9, Suit::Clubs)];
+        assert_eq!(BlackjackApp::calculate_hand_value(&hand, rules), 21);
+        assert!(BlackjackApp::is_soft(&hand, rules));
+
+        let hard = [card(Value::King, Suit::Spades), n(6, Suit::Hearts), card(Value::Ace, Suit::Clubs)];
+        assert_eq!(BlackjackApp::calculate_hand_value(&hard, rules), 17);
+        assert!(!BlackjackApp::is_soft(&hard, rules));
+    }
+
+    #[test]
+    fn standing_on_a_better_hand_wins_even_money() {
+        use Suit::*;
+        let mut app = scripted(vec![n(10, Spades), n(7, Clubs), n(9, Hearts), n(10, Diamonds)]);
+        assert_eq!(app.total_money, 90, "the wager leaves the bankroll at the deal");
+        app.stand().unwrap();
+        assert!(settled(&app));
+        assert_eq!(app.outcomes, vec![Outcome::Won]);
+        assert_eq!(app.total_money, 110);
+    }
+
+    #[test]
+    fn busting_loses_the_bet_without_the_dealer_drawing() {
+        use Suit::*;
+        let mut app = scripted(vec![n(10, Spades), n(9, Clubs), n(6, Hearts), n(8, Diamonds), card(Value::King, Spades), n(2, Hearts)]);
+        app.hit().unwrap();
+        assert!(settled(&app));
+        assert_eq!(app.outcomes, vec![Outcome::Busted]);
+        assert_eq!(app.dealer_hand.len(), 2);
+        assert_eq!(app.total_money, 90);
+    }
+
+    #[test]
+    fn equal_totals_push_and_return_the_bet() {
+        use Suit::*;
+        let mut app = scripted(vec![n(10, Spades), n(10, Clubs), n(8, Hearts), n(8, Diamonds)]);
+        app.stand().unwrap();
+        assert_eq!(app.outcomes, vec![Outcome::Push]);
+        assert_eq!(app.total_money, 100);
+    }
+
+    #[test]
+    fn a_natural_pays_three_to_two_as_soon_as_it_is_dealt() {
+        use Suit::*;
+        let app = scripted(vec![card(Value::Ace, Spades), n(9, Clubs), card(Value::King, Hearts), n(7, Diamonds)]);
+        assert!(settled(&app), "there is nothing to decide on a natural");
+        assert_eq!(app.outcomes, vec![Outcome::Blackjack]);
+        assert_eq!(app.total_money, 115);
+        assert_eq!(app.round_history.last().unwrap().net, 15);
+    }
+
+    #[test]
+    fn a_split_settles_each_hand_on_its_own() {
+        use Suit::*;
+        let mut app = scripted(vec![
+            n(8, Spades), n(10, Clubs), n(8, Hearts), n(7, Diamonds), // 8-8 against 17
+            n(3, Clubs), card(Value::King, Spades), // One card to each split hand
+            n(5, Hearts), // The first hand hits to 16
+        ]);
+        app.split().unwrap();
+        assert_eq!(app.total_money, 80, "the split stakes a second bet");
+        app.hit().unwrap();
+        app.stand().unwrap();
+        app.stand().unwrap();
+        assert!(settled(&app));
+        assert_eq!(app.outcomes, vec![Outcome::Lost, Outcome::Won]);
+        assert_eq!(app.total_money, 100);
+        assert_eq!(app.round_history.last().unwrap().net, 0);
+    }
+
+    #[test]
+    fn the_dealer_draws_from_reshuffled_discards_when_the_deck_runs_dry() {
+        use Suit::*;
+        let mut app = scripted(vec![n(10, Spades), n(6, Clubs), n(10, Hearts), n(5, Diamonds)]);
+        app.discards = vec![n(7, Clubs)];
+        app.stand().unwrap();
+        assert!(settled(&app));
+        assert_eq!(app.dealer_hand.len(), 3, "the dealer finished 11 with the reshuffled seven");
+        assert_eq!(app.outcomes, vec![Outcome::Won]);
+    }
+
+    #[test]
+    fn a_dry_deck_voids_the_round_under_the_void_policy() {
+        use Suit::*;
+        let rules = RuleSet { low_deck_policy: LowDeckPolicy::VoidRound, ..RuleSet::default() };
+        let mut app = scripted_with(vec![n(10, Spades), n(6, Clubs), n(10, Hearts), n(5, Diamonds)], rules);
+        app.discards = vec![n(7, Clubs)];
+        app.stand().unwrap();
+        assert!(settled(&app));
+        assert_eq!(app.dealer_hand.len(), 2);
+        assert_eq!(app.total_money, 100, "a void round returns the bet");
+    }
+
+    #[test]
+    fn hands_print_as_a_single_line_with_the_total() {
+        use Suit::*;
+        let rules = ValueRules::default();
+        assert_eq!(hand_to_string(&[card(Value::Ace, Spades), card(Value::King, Hearts)], rules), "A♠ K♥ (21)");
+        assert_eq!(hand_to_string(&[n(10, Clubs), n(6, Diamonds), card(Value::Queen, Spades)], rules), "10♣ 6♦ Q♠ (26)");
+    }
+
+    #[test]
+    fn twenty_one_on_split_aces_pays_even_money() {
+        use Suit::*;
+        let rules = RuleSet { split_aces_locked: true, ..RuleSet::default() };
+        let mut app = scripted_with(
+            vec![card(Value::Ace, Spades), n(9, Clubs), card(Value::Ace, Hearts), n(8, Diamonds), card(Value::King, Spades), card(Value::Queen, Hearts)],
+            rules,
+        );
+        app.split().unwrap();
+        assert!(settled(&app), "split aces take one card each");
+        assert!(!app.is_natural(0) && !app.is_natural(1));
+        assert_eq!(app.outcomes, vec![Outcome::Won, Outcome::Won]);
+        assert_eq!(app.total_money, 120);
+    }
+
+    #[test]
+    fn a_pair_cannot_be_split_past_the_cap() {
+        use Suit::*;
+        let rules = RuleSet { max_splits: 1, ..RuleSet::default() };
+        let mut app = scripted_with(vec![n(8, Spades), n(10, Clubs), n(8, Hearts), n(7, Diamonds), n(8, Clubs), card(Value::King, Spades)], rules);
+        assert!(app.can_split_now());
+        app.split().unwrap();
+        assert!(BlackjackApp::can_split(&app.player_hands[0]), "the first hand drew another eight");
+        assert!(!app.can_split_now());
+        assert!(!app.legal_actions().contains(&Action::Split));
+        assert_eq!(app.split(), Err(GameError::IllegalAction));
+    }
+
+    #[test]
+    fn peek_next_shows_the_cards_about_to_be_drawn() {
+        use Suit::*;
+        let mut app = scripted(vec![n(10, Spades), n(7, Clubs), n(2, Hearts), n(9, Diamonds), n(3, Clubs), n(4, Hearts), n(5, Spades)]);
+        let upcoming: Vec<String> = app.peek_next(2).iter().rev().map(|c| c.short()).collect();
+        assert_eq!(upcoming, ["3♣", "4♥"]);
+        app.hit().unwrap();
+        app.hit().unwrap();
+        let drawn: Vec<String> = app.player_hands[0][2..].iter().map(|c| c.short()).collect();
+        assert_eq!(drawn, upcoming);
+        assert_eq!(app.peek_next(5).len(), 1, "only what's left in the deck");
+    }
+
+    #[test]
+    fn a_short_bankroll_splits_once_but_not_twice() {
+        use Suit::*;
+        let mut app = scripted(vec![n(8, Spades), n(10, Clubs), n(8, Hearts), n(7, Diamonds), n(8, Clubs), card(Value::King, Spades)]);
+        app.total_money = 10; // Exactly one more bet
+        app.split().unwrap();
+        assert_eq!(app.total_money, 0);
+        assert!(BlackjackApp::can_split(&app.player_hands[0]));
+        assert_eq!(app.legal_actions(), vec![Action::Hit, Action::Stand]);
+        assert_eq!(app.split(), Err(GameError::InsufficientFunds));
+        assert_eq!(app.double_down(), Err(GameError::InsufficientFunds));
+    }
+
+    #[test]
+    fn the_beat_best_hand_dealer_draws_past_seventeen() {
+        use Suit::*;
+        let rules = RuleSet { dealer_play: DealerPlay::BeatBestHand, ..RuleSet::default() };
+        let mut app = scripted_with(vec![n(10, Spades), n(10, Clubs), card(Value::King, Hearts), n(7, Diamonds), n(2, Hearts), n(2, Clubs)], rules);
+        app.stand().unwrap();
+        assert!(settled(&app));
+        assert_eq!(app.dealer_hand.len(), 4, "17 and 19 both trail the player's 20");
+        assert_eq!(app.dealer_value(), 21);
+        assert_eq!(app.outcomes, vec![Outcome::Lost]);
+    }
+
+    #[test]
+    fn illegal_actions_report_why_they_failed() {
+        use Suit::*;
+        let mut app = scripted(vec![n(10, Spades), n(7, Clubs), n(9, Hearts), n(10, Diamonds)]);
+        assert_eq!(app.split(), Err(GameError::IllegalAction), "not a pair");
+        assert_eq!(app.place_bet(), Err(GameError::IllegalAction), "a round is in play");
+        app.stand().unwrap();
+
+        assert_eq!(app.stand(), Err(GameError::IllegalAction));
+        assert_eq!(app.hit(), Err(GameError::IllegalAction));
+        assert_eq!(app.double_down(), Err(GameError::IllegalAction));
+        assert_eq!(app.total_money, 110, "nothing was settled twice");
+        app.wager = 500;
+        assert_eq!(app.place_bet(), Err(GameError::InsufficientFunds));
+    }
+
+    #[test]
+    fn doubling_without_the_money_is_refused() {
+        use Suit::*;
+        let mut app = scripted(vec![n(6, Spades), n(7, Clubs), n(5, Hearts), n(10, Diamonds)]);
+        app.total_money = 5;
+        assert_eq!(app.double_down(), Err(GameError::InsufficientFunds));
+        assert_eq!(app.player_hands[0].len(), 2);
+    }
+
+    #[test]
+    fn hitting_a_dry_deck_voids_the_round() {
+        use Suit::*;
+        let rules = RuleSet { low_deck_policy: LowDeckPolicy::VoidRound, ..RuleSet::default() };
+        let mut app = scripted_with(vec![n(6, Spades), n(7, Clubs), n(5, Hearts), n(10, Diamonds)], rules);
+        assert_eq!(app.hit(), Err(GameError::DeckEmpty));
+        assert!(settled(&app));
+        assert_eq!(app.total_money, 100);
+    }
+
+    #[test]
+    fn the_shoe_persists_between_rounds() {
+        let mut app = BlackjackApp::with_seed(7);
+        assert_eq!(app.shoe_size, 312);
+        while matches!(app.game_state, GameState::PlayerTurn) {
+            app.stand().unwrap();
+        }
+        let left = app.cards_remaining();
+        app.place_bet().unwrap();
+        assert!(app.cards_remaining() < left, "the next round deals on from the same shoe");
+        assert!(app.check_deal().is_ok());
+    }
+
+    #[test]
+    fn each_shoe_keeps_its_own_net() {
+        use Suit::*;
+        let mut app = scripted(vec![n(10, Spades), n(7, Clubs), n(9, Hearts), n(10, Diamonds)]);
+        app.stand().unwrap();
+        assert_eq!(app.current_shoe_net(), 10);
+
+        app.place_bet().unwrap(); // The scripted shoe is used up, so this deals from a new one
+        while matches!(app.game_state, GameState::PlayerTurn) {
+            app.stand().unwrap();
+        }
+        let second = app.round_history.last().unwrap().net;
+        assert_eq!(app.shoe_results, vec![10, second]);
+        assert_eq!(app.current_shoe_net(), second);
+
+        app.new_game();
+        assert!(app.shoe_results.is_empty(), "a new game doesn't carry over the last game's shoes");
+    }
+
+    #[test]
+    fn a_continuous_shuffler_deals_every_round_from_a_full_shoe() {
+        let mut app = BlackjackApp::with_seed_and_rules(5, RuleSet { csm: true, ..RuleSet::default() });
+        app.total_money = 10_000;
+        for _ in 0..30 {
+            assert_eq!(app.shoe_size, 312);
+            assert_eq!(app.cards_remaining(), 308, "only the opening deal is out of the shoe");
+            assert_eq!(app.opening_count, 0.0, "the count never moves off zero");
+            while matches!(app.game_state, GameState::PlayerTurn) {
+                app.stand().unwrap();
+            }
+            app.place_bet().unwrap();
+        }
+        assert!(!app.should_show_shoe_count());
+        assert_eq!(app.shoe_results.len(), 1);
+    }
+
+    #[test]
+    fn replaying_the_event_log_rebuilds_the_game() {
+        let mut app = BlackjackApp::with_seed(11);
+        app.advance_delay = 0.0;
+        for _ in 0..40 {
+            while matches!(app.game_state, GameState::PlayerTurn) {
+                if app.current_hand_value() < 15 {
+                    app.hit().unwrap();
+                } else {
+                    app.stand().unwrap();
+                }
+            }
+            if app.total_money < app.wager {
+                break;
+            }
+            app.place_bet().unwrap();
+        }
+
+        let replayed = BlackjackApp::replay_events(11, RuleSet::default(), app.events()).unwrap();
+        assert_eq!(replayed.total_money, app.total_money);
+        assert_eq!(replayed.rounds_played, app.rounds_played);
+        assert_eq!(replayed.cards_remaining(), app.cards_remaining());
+        assert_eq!(replayed.events().len(), app.events().len());
+    }
+
+    #[test]
+    fn the_dealer_never_stands_below_seventeen() {
+        let mut app = BlackjackApp::with_seed(3);
+        app.advance_delay = 0.0;
+        app.total_money = 1_000_000;
+        for _ in 0..300 {
+            while matches!(app.game_state, GameState::PlayerTurn) {
+                app.stand().unwrap();
+            }
+            let dealer_value = BlackjackApp::calculate_hand_value(&app.dealer_hand, app.rules.value_rules);
+            if !app.is_natural(0) {
+                assert!(dealer_value >= 17, "dealer stood on {}", dealer_value);
+            }
+            app.place_bet().unwrap();
+        }
+    }
+
+    #[test]
+    fn aces_low_mode_counts_every_ace_as_one() {
+        use Suit::*;
+        let hand = [card(Value::Ace, Spades), card(Value::Ace, Hearts), n(9, Clubs)];
+        assert_eq!(BlackjackApp::calculate_hand_value(&hand, ValueRules::default()), 21);
+        assert_eq!(BlackjackApp::calculate_hand_value(&hand, ValueRules { aces_low_only: true }), 11);
+    }
+
+    #[test]
+    fn every_shuffle_kind_keeps_the_same_cards() {
+        let key = |card: &Card| (card.suit as u8, card.value());
+        let mut expected: Vec<_> = BlackjackApp::create_deck(2).iter().map(|c| (key(c), c.rank_str())).collect();
+        expected.sort();
+        for (kind, name) in [(ShuffleKind::Perfect, "perfect"), (ShuffleKind::Riffle { passes: 7 }, "riffle"), (ShuffleKind::Overhand { passes: 4 }, "overhand")] {
+            let mut deck = BlackjackApp::create_deck(2);
+            kind.apply(&mut deck, &mut StdRng::seed_from_u64(9));
+            let mut shuffled: Vec<_> = deck.iter().map(|c| (key(c), c.rank_str())).collect();
+            shuffled.sort();
+            assert_eq!(shuffled, expected, "the {} shuffle lost or duplicated a card", name);
+        }
+    }
+
+    #[test]
+    fn typed_bets_are_validated() {
+        let mut app = BlackjackApp::undealt(0, RuleSet { chip_denomination: 5, ..RuleSet::default() });
+        assert_eq!(app.parse_bet(" 25 "), Ok(25));
+        assert_eq!(app.parse_bet("-5"), Err(String::from("Bets can't be negative")));
+        assert_eq!(app.parse_bet("ten"), Err(String::from("Enter a whole number")));
+        assert_eq!(app.parse_bet(""), Err(String::from("Enter a whole number")));
+        assert_eq!(app.parse_bet("0"), Err(String::from("The table minimum is $5")));
+        assert_eq!(app.parse_bet("12"), Err(String::from("Bets go down in chips of $5")));
+        app.total_money = 20;
+        assert_eq!(app.parse_bet("25"), Err(String::from("You only have $20")));
+    }
+
+    #[test]
+    fn a_forced_upcard_is_dealt_every_round() {
+        let mut app = BlackjackApp::undealt(21, RuleSet { num_decks: 1, ..RuleSet::default() });
+        app.advance_delay = 0.0;
+        app.total_money = 10_000;
+        app.force_dealer_upcard(Some(Value::Number(6)));
+        for _ in 0..200 {
+            // Four sixes to a shoe, so most rounds start after the shoe has run out of them
+            app.place_bet().unwrap();
+            assert!(app.dealer_hand[0].value == Value::Number(6));
+            while matches!(app.game_state, GameState::PlayerTurn) {
+                app.stand().unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn the_round_limit_reshuffles_a_shoe_with_plenty_left() {
+        let rules = RuleSet { max_rounds_per_shoe: Some(3), ..RuleSet::default() };
+        let mut app = BlackjackApp::with_seed_and_rules(4, rules);
+        app.advance_delay = 0.0;
+        for round in 1..=4 {
+            assert_eq!(app.shoe_results.len(), if round <= 3 { 1 } else { 2 }, "round {}", round);
+            while matches!(app.game_state, GameState::PlayerTurn) {
+                app.stand().unwrap();
+            }
+            assert!(!app.needs_reshuffle, "the cut card is nowhere near");
+            app.place_bet().unwrap();
+        }
+        assert_eq!(app.shoe_rounds, 2);
+    }
+
+    #[test]
+    fn hand_queries_follow_the_scripted_cards() {
+        use Suit::*;
+        let mut app = scripted(vec![card(Value::Ace, Spades), n(9, Clubs), n(6, Hearts), n(10, Diamonds), n(10, Spades), card(Value::King, Hearts)]);
+        assert_eq!(app.current_hand_value(), 17);
+        assert!(app.current_hand_is_soft());
+        assert!(!app.current_hand_busted());
+        assert_eq!(app.dealer_value(), 9, "only the upcard counts while the player acts");
+
+        app.hit().unwrap();
+        assert_eq!(app.current_hand_value(), 17);
+        assert!(!app.current_hand_is_soft());
+        app.hit().unwrap();
+        assert!(app.current_hand_busted());
+        assert_eq!(app.dealer_value(), 19, "the hole card counts once the round is over");
+    }
+
+    #[test]
+    fn the_dealer_skips_drawing_when_every_split_hand_busts() {
+        use Suit::*;
+        let mut app = scripted(vec![
+            n(8, Spades), n(10, Clubs), n(8, Hearts), n(6, Diamonds), // 8-8 against 16
+            n(5, Clubs), n(6, Hearts), // 13 and 14 after the split
+            card(Value::King, Spades), card(Value::Queen, Diamonds), // Both bust
+            n(3, Spades), // Would make the dealer 19
+        ]);
+        app.split().unwrap();
+        app.hit().unwrap();
+        app.hit().unwrap();
+        assert!(settled(&app));
+        assert_eq!(app.outcomes, vec![Outcome::Busted, Outcome::Busted]);
+        assert_eq!(app.dealer_hand.len(), 2);
+        assert_eq!(app.cards_remaining(), 1);
+    }
+
+    #[test]
+    fn the_state_reads_out_as_text() {
+        use Suit::*;
+        let mut app = scripted(vec![n(10, Spades), n(7, Clubs), n(9, Hearts), n(10, Diamonds)]);
+        assert_eq!(
+            app.describe_state(),
+            "Your turn, hand 1 of 1: 10♠ 9♥, total 19 (hard). Bet $10. Dealer shows 7♣. You can: Hit, Stand, Double Down."
+        );
+        app.stand().unwrap();
+        assert_eq!(
+            app.describe_state(),
+            "Round Over: Hand 1 Won! Dealer 7♣ 10♦ = 17 (hard). You had hand 1 10♠ 9♥ = 19 (hard). Bankroll $110."
+        );
+    }
+
+    #[test]
+    fn betting_shows_how_the_previous_round_went() {
+        use Suit::*;
+        let mut app = scripted(vec![
+            n(8, Spades), n(10, Clubs), n(8, Hearts), n(7, Diamonds),
+            n(3, Clubs), card(Value::King, Spades),
+            n(5, Hearts),
+        ]);
+        assert_eq!(app.previous_round_summary(), None);
+        app.split().unwrap();
+        app.hit().unwrap();
+        app.stand().unwrap();
+        app.stand().unwrap();
+        assert_eq!(app.previous_round_summary().as_deref(), Some("Previous round: Lost, Won, net +$0"));
+        app.new_game();
+        assert_eq!(app.previous_round_summary(), None);
+    }
+
+    #[test]
+    fn penetration_is_the_share_of_the_shoe_dealt() {
+        let mut app = BlackjackApp::undealt(0, RuleSet::default());
+        assert_eq!(app.shoe_penetration(), 0.0, "no shoe before the first deal");
+        app.place_bet().unwrap();
+        assert_eq!(app.shoe_size, 312);
+        let dealt = 312 - app.cards_remaining();
+        assert!((app.shoe_penetration() - dealt as f32 / 312.0).abs() < 1e-6);
+        app.deck.truncate(78);
+        assert!((app.shoe_penetration() - 0.75).abs() < 1e-6);
+        assert_eq!(app.cut_card_mark(), Some(0.75), "the dealt share has reached the tick");
+        app.rules.csm = true;
+        assert_eq!(app.cut_card_mark(), None);
+    }
+
+    #[test]
+    fn nine_ten_eleven_tables_refuse_a_hard_eight() {
+        use Suit::*;
+        let rules = RuleSet { double_restriction: DoubleRule::NineTenEleven, ..RuleSet::default() };
+        let mut eight = scripted_with(vec![n(5, Spades), n(7, Clubs), n(3, Hearts), n(10, Diamonds)], rules.clone());
+        assert!(!eight.legal_actions().contains(&Action::DoubleDown));
+        assert_eq!(eight.double_down(), Err(GameError::IllegalAction));
+
+        let mut ten = scripted_with(vec![n(6, Spades), n(7, Clubs), n(4, Hearts), n(10, Diamonds), n(9, Clubs)], rules);
+        assert!(ten.legal_actions().contains(&Action::DoubleDown));
+        ten.double_down().unwrap();
+        assert_eq!(ten.player_bets, vec![20]);
+    }
+
+    #[test]
+    fn an_imported_session_replays_every_round() {
+        let mut app = BlackjackApp::with_seed(13);
+        app.advance_delay = 0.0;
+        for round in 0..6 {
+            while matches!(app.game_state, GameState::PlayerTurn) {
+                if app.current_hand_value() < 13 {
+                    app.hit().unwrap();
+                } else {
+                    app.stand().unwrap();
+                }
+            }
+            app.wager = if round % 2 == 0 { 20 } else { 10 };
+            app.place_bet().unwrap();
+        }
+        while matches!(app.game_state, GameState::PlayerTurn) {
+            app.stand().unwrap();
+        }
+        let imported = BlackjackApp::import_session(&app.export_session());
+        assert_eq!(imported.seed, 13);
+        assert!(matches!(imported.game_state, GameState::Betting), "nothing is dealt or staked after the replay");
+        let outcomes = |app: &BlackjackApp| app.round_history.iter().map(|round| (round.outcomes.clone(), round.net)).collect::<Vec<_>>();
+        assert_eq!(outcomes(&imported), outcomes(&app));
+        assert_eq!(imported.total_money, app.total_money);
+        assert_eq!(imported.rounds_played, 7);
+
+        let mut other = BlackjackApp::with_seed(99);
+        other.training_mode = true;
+        other.show_player_total = false;
+        other.load_session(&app.export_session());
+        assert!(other.training_mode && !other.show_player_total, "loading a session keeps the display settings");
+        assert_eq!(other.total_money, app.total_money);
+        assert_eq!(other.round_history.len(), 7);
+    }
+
+    #[test]
+    fn stats_export_as_csv_with_a_header() {
+        use Suit::*;
+        let mut app = scripted(vec![n(10, Spades), n(7, Clubs), n(9, Hearts), n(10, Diamonds)]);
+        app.stand().unwrap();
+        assert_eq!(app.stats_to_csv(), "round,bet,outcome,net,bankroll\n1,10,Won,10,110\n");
+    }
+
+    #[test]
+    fn an_inconsistent_round_is_reset_instead_of_settled() {
+        use Suit::*;
+        let mut app = scripted(vec![n(10, Spades), n(7, Clubs), n(9, Hearts), n(10, Diamonds)]);
+        app.split_hands.push(false); // A split flag with no hand behind it
+        app.stand().unwrap();
+        match &app.game_state {
+            GameState::GameOver(message) => assert!(message.starts_with("Round Reset"), "{}", message),
+            _ => panic!("the round should have been closed"),
+        }
+        assert!(app.outcomes.is_empty());
+        assert_eq!(app.total_money, 100, "the stake comes back");
+        assert!(app.check_consistency().is_ok());
+    }
+
+    #[test]
+    fn burned_cards_are_set_aside_after_a_reshuffle() {
+        let mut app = BlackjackApp::undealt(8, RuleSet { burn_cards: 3, ..RuleSet::default() });
+        app.place_bet().unwrap();
+        let on_table = app.player_hands.iter().flatten().count() + app.dealer_hand.len();
+        assert_eq!(app.burned.len(), 3);
+        assert_eq!(app.cards_remaining() + app.burned.len() + on_table, app.shoe_size);
+    }
+
+    #[test]
+    fn the_rule_summary_follows_the_rules() {
+        let mut rules = RuleSet::default();
+        assert!(rules.describe().contains(&String::from("Double after split allowed")));
+        rules.double_after_split = false;
+        let lines = rules.describe();
+        assert!(lines.contains(&String::from("No double after split")));
+        assert!(!lines.contains(&String::from("Double after split allowed")));
+    }
+
+    #[test]
+    fn two_naturals_push_with_their_own_reason() {
+        use Suit::*;
+        let app = scripted(vec![card(Value::Ace, Spades), card(Value::Ace, Clubs), card(Value::King, Hearts), card(Value::Queen, Diamonds)]);
+        assert!(settled(&app));
+        assert_eq!(app.outcomes, vec![Outcome::BlackjackPush]);
+        assert_eq!(app.round_history.last().unwrap().outcomes, vec![Outcome::BlackjackPush]);
+        assert!(app.describe_state().contains("Both blackjack - push."));
+        assert_eq!(app.total_money, 100);
+    }
+
+    #[test]
+    fn a_peeked_dealer_natural_ends_the_round_before_the_player_acts() {
+        use Suit::*;
+        let rules = RuleSet { dealer_peek: true, ..RuleSet::default() };
+        let app = scripted_with(vec![n(10, Spades), card(Value::Ace, Clubs), n(9, Hearts), card(Value::King, Diamonds)], rules.clone());
+        assert!(settled(&app));
+        assert!(app.round_actions.is_empty());
+        assert_eq!(app.outcomes, vec![Outcome::Lost]);
+        assert!(app.describe_state().contains("Dealer A♣ K♦"), "both dealer cards are shown");
+
+        let app = scripted_with(vec![card(Value::Ace, Spades), card(Value::Ace, Clubs), card(Value::King, Hearts), card(Value::King, Diamonds)], rules);
+        assert_eq!(app.outcomes, vec![Outcome::BlackjackPush], "a player natural still pushes");
+    }
+
+    #[test]
+    fn a_shoe_too_short_for_the_deal_is_replaced_first() {
+        let mut app = BlackjackApp::with_seed(6);
+        app.advance_delay = 0.0;
+        while matches!(app.game_state, GameState::PlayerTurn) {
+            app.stand().unwrap();
+        }
+        app.deck.truncate(3);
+        app.needs_reshuffle = false; // Only the short shoe can trigger it
+        app.place_bet().unwrap();
+        assert_eq!(app.shoe_results.len(), 2);
+        assert_eq!(app.cards_remaining(), app.shoe_size - 4);
+    }
+
+    #[test]
+    fn split_hands_cannot_double_without_das() {
+        use Suit::*;
+        let rules = RuleSet { double_after_split: false, ..RuleSet::default() };
+        let mut app = scripted_with(vec![n(5, Spades), n(10, Clubs), n(5, Hearts), n(7, Diamonds), n(6, Clubs), n(5, Diamonds)], rules);
+        assert!(app.legal_actions().contains(&Action::DoubleDown), "the unsplit pair may double");
+        app.split().unwrap();
+        assert!(!app.legal_actions().contains(&Action::DoubleDown));
+        assert_eq!(app.double_down(), Err(GameError::IllegalAction));
+        app.stand().unwrap();
+        assert!(!app.legal_actions().contains(&Action::DoubleDown));
+    }
+
+    #[test]
+    fn a_locked_seed_replays_the_same_cards_under_other_rules() {
+        fn play(app: &mut BlackjackApp) -> Vec<String> {
+            app.total_money = 10_000;
+            let mut dealt = Vec::new();
+            for _ in 0..60 {
+                app.place_bet().unwrap();
+                while matches!(app.game_state, GameState::PlayerTurn) {
+                    app.stand().unwrap();
+                }
+                dealt.push(hand_to_string(&app.dealer_hand, app.rules.value_rules));
+            }
+            dealt
+        }
+
+        let mut app = BlackjackApp::undealt(99, RuleSet::default());
+        app.lock_seed = true;
+        let first = play(&mut app);
+        let first_bankroll = app.total_money;
+        app.new_game();
+        app.rules.dealer_22_pushes = true;
+        let second = play(&mut app);
+        assert_eq!(first, second);
+        assert!(first.iter().any(|dealer| dealer.ends_with("(22)")), "the seed needs a dealer 22 to tell the rules apart");
+        assert!(app.total_money < first_bankroll, "a dealer 22 no longer pays the standing hands");
+    }
+
+    #[test]
+    fn four_aces_total_fourteen_and_resplit_into_separate_hands() {
+        use Suit::*;
+        let aces = [card(Value::Ace, Spades), card(Value::Ace, Hearts), card(Value::Ace, Clubs), card(Value::Ace, Diamonds)];
+        assert_eq!(BlackjackApp::calculate_hand_value(&aces, ValueRules::default()), 14);
+
+        let rules = RuleSet { max_ace_splits: 2, ..RuleSet::default() };
+        let mut app = scripted_with(
+            vec![aces[0].clone(), n(9, Clubs), aces[1].clone(), n(8, Diamonds), aces[2].clone(), n(5, Hearts), n(7, Spades), n(6, Spades)],
+            rules,
+        );
+        app.split().unwrap();
+        assert_eq!(app.player_hands[0].iter().map(|c| c.short()).collect::<Vec<_>>(), ["A♠", "A♣"]);
+        app.split().unwrap();
+        let hands: Vec<String> = app.player_hands.iter().map(|hand| hand.iter().map(|c| c.short()).collect::<Vec<_>>().join(" ")).collect();
+        assert_eq!(hands, ["A♠ 7♠", "A♥ 5♥", "A♣ 6♠"]);
+        assert_eq!(app.total_money, 70, "three hands staked");
+    }
+
+    #[test]
+    fn a_restored_snapshot_plays_on_identically() {
+        let rules = RuleSet { blind_first_card: true, ..RuleSet::default() };
+        let mut app = BlackjackApp::with_seed_and_rules(17, rules.clone());
+        app.advance_delay = 0.0;
+        while !matches!(app.game_state, GameState::PlayerTurn) {
+            app.place_bet().unwrap();
+        }
+        let json = serde_json::to_string(&app.snapshot()).unwrap();
+        let mut restored = BlackjackApp::undealt(0, rules);
+        restored.advance_delay = 0.0;
+        restored.restore(serde_json::from_str(&json).unwrap());
+        assert!(restored.first_card_hidden);
+
+        for game in [&mut app, &mut restored] {
+            let _ = game.hit();
+            if matches!(game.game_state, GameState::PlayerTurn) {
+                game.stand().unwrap();
+            }
+        }
+        assert_eq!(restored.describe_state(), app.describe_state());
+        assert_eq!(restored.total_money, app.total_money);
+        assert_eq!(restored.bonus_winnings, app.bonus_winnings);
+        assert_eq!(restored.dealer_play_summary(), app.dealer_play_summary());
+    }
+
+    #[test]
+    fn aces_and_other_pairs_have_their_own_split_caps() {
+        use Suit::*;
+        let mut aces = scripted(vec![card(Value::Ace, Spades), n(9, Clubs), card(Value::Ace, Hearts), n(8, Diamonds), card(Value::Ace, Clubs), n(5, Hearts)]);
+        aces.split().unwrap();
+        assert!(BlackjackApp::can_split(&aces.player_hands[0]));
+        assert!(!aces.can_split_now(), "aces split once by default");
+
+        let rules = RuleSet { max_splits: 2, max_ace_splits: 1, ..RuleSet::default() };
+        let mut eights = scripted_with(
+            vec![
+                n(8, Spades), n(10, Clubs), n(8, Hearts), n(7, Diamonds),
+                n(8, Clubs), n(2, Hearts), // First split: the first hand pairs up again
+                n(8, Diamonds), n(3, Hearts), // Second split, the last the table allows
+            ],
+            rules,
+        );
+        for split in 1..=2 {
+            assert!(eights.can_split_now(), "split {} is within the cap", split);
+            eights.split().unwrap();
+        }
+        assert_eq!(eights.player_hands.len(), 3);
+        assert!(BlackjackApp::can_split(&eights.player_hands[0]));
+        assert!(!eights.can_split_now(), "the general cap stops a third split");
+    }
+
+    #[test]
+    fn a_double_loses_in_full_to_a_no_hole_card_blackjack() {
+        use Suit::*;
+        let peek = RuleSet { dealer_peek: true, ..RuleSet::default() };
+        let mut american = scripted_with(vec![n(6, Spades), card(Value::Ace, Clubs), n(5, Hearts), card(Value::King, Spades), n(9, Diamonds)], peek);
+        assert!(settled(&american), "the peek ends the round before a double");
+        assert_eq!(american.double_down(), Err(GameError::IllegalAction));
+        assert_eq!(american.total_money, 90);
+
+        let nhc = RuleSet { hole_card: HoleCard::EuropeanNHC, ..RuleSet::default() };
+        let mut european = scripted_with(vec![n(6, Spades), card(Value::Ace, Clubs), n(5, Hearts), n(9, Diamonds), card(Value::King, Spades)], nhc);
+        assert_eq!(european.dealer_hand.len(), 1);
+        european.double_down().unwrap();
+        assert!(settled(&european));
+        assert!(european.dealer_has_natural());
+        assert_eq!(european.outcomes, vec![Outcome::Lost]);
+        assert_eq!(european.total_money, 80, "the doubled stake is lost too");
+    }
+
+    #[test]
+    fn kelly_bets_follow_the_edge() {
+        assert_eq!(BlackjackApp::kelly_bet(10_000, 0.02, 5, 500), 150, "about 1.5% of the bankroll, in whole chips");
+        assert_eq!(BlackjackApp::kelly_bet(1_000_000, 0.02, 5, 500), 500, "capped at the table maximum");
+        assert_eq!(BlackjackApp::kelly_bet(10_000, -0.005, 5, 500), 5, "no edge means the minimum");
+    }
+
+    #[test]
+    fn the_blind_card_hides_the_total_until_the_player_stands() {
+        use Suit::*;
+        let rules = RuleSet { blind_first_card: true, ..RuleSet::default() };
+        let mut app = scripted_with(vec![n(10, Spades), n(7, Clubs), n(9, Hearts), n(10, Diamonds)], rules);
+        assert!(app.is_card_hidden(0, 0));
+        assert_eq!(app.player_total_label(0), "?");
+        assert!(app.describe_state().contains("? 9♥, total ?"));
+        app.stand().unwrap();
+        assert!(!app.is_card_hidden(0, 0));
+        assert_eq!(app.player_total_label(0), "19 (hard)");
+    }
+
+    #[test]
+    fn a_six_card_charlie_wins_and_five_cards_do_not() {
+        use Suit::*;
+        let rules = RuleSet { charlie: Some(6), ..RuleSet::default() };
+        let small = vec![n(2, Spades), n(10, Clubs), n(3, Hearts), n(9, Diamonds), n(2, Clubs), n(3, Spades), n(4, Hearts), n(5, Clubs)];
+        let mut six = scripted_with(small.clone(), rules.clone());
+        for _ in 0..4 {
+            six.hit().unwrap();
+        }
+        assert!(settled(&six), "six cards without busting end the hand");
+        assert_eq!(six.outcomes, vec![Outcome::Won], "19 wins by Charlie against the dealer's 19");
+
+        let mut five = scripted_with(small, rules);
+        for _ in 0..3 {
+            five.hit().unwrap();
+        }
+        assert!(matches!(five.game_state, GameState::PlayerTurn));
+        five.stand().unwrap();
+        assert_eq!(five.outcomes, vec![Outcome::Lost], "14 on five cards is just 14");
+    }
+
+    struct TwoCardTwenty;
+
+    impl HandBonus for TwoCardTwenty {
+        fn name(&self) -> String {
+            String::from("Two-card 20")
+        }
+
+        fn matches(&self, hand: &[Card], _split: bool, value_rules: ValueRules) -> bool {
+            hand.len() == 2 && BlackjackApp::calculate_hand_value(hand, value_rules) == 20
+        }
+
+        fn pays(&self) -> usize {
+            4
+        }
+    }
+
+    #[test]
+    fn a_registered_bonus_pays_on_a_matching_hand() {
+        use Suit::*;
+        let mut app = scripted(vec![card(Value::King, Spades), n(7, Clubs), n(10, Diamonds), card(Value::Queen, Hearts)]);
+        app.add_bonus(Box::new(TwoCardTwenty)); // Bonuses are checked at settlement
+        app.stand().unwrap();
+        assert_eq!(app.outcomes, vec![Outcome::Won]);
+        assert_eq!(app.bonus_winnings, vec![30], "4:1 instead of 1:1");
+        assert_eq!(app.total_money, 140);
+    }
+
+    #[test]
+    fn a_suited_21_after_a_split_earns_no_bonus() {
+        use Suit::*;
+        let rules = RuleSet { bonuses: vec![BonusKind::SuitedBlackjack], split_aces_locked: true, ..RuleSet::default() };
+        let mut app = scripted_with(
+            vec![card(Value::Ace, Spades), n(9, Clubs), card(Value::Ace, Hearts), n(8, Diamonds), card(Value::King, Spades), n(2, Hearts)],
+            rules,
+        );
+        app.split().unwrap();
+        assert!(settled(&app));
+        assert_eq!(app.outcomes, vec![Outcome::Won, Outcome::Lost]);
+        assert_eq!(app.bonus_winnings, vec![0, 0]);
+        assert_eq!(app.total_money, 100);
+    }
+
+    #[test]
+    fn a_manual_reshuffle_keeps_money_and_stats() {
+        let mut app = BlackjackApp::with_seed(12);
+        app.advance_delay = 0.0;
+        for _ in 0..8 {
+            while matches!(app.game_state, GameState::PlayerTurn) {
+                app.stand().unwrap();
+            }
+            app.place_bet().unwrap();
+        }
+        while matches!(app.game_state, GameState::PlayerTurn) {
+            app.stand().unwrap();
+        }
+        let (money, rounds, history) = (app.total_money, app.rounds_played, app.round_history.len());
+        app.reshuffle_now();
+        assert_eq!((app.total_money, app.rounds_played, app.round_history.len()), (money, rounds, history));
+        assert!(app.discards.is_empty());
+        let on_table = app.player_hands.iter().flatten().count() + app.dealer_hand.len();
+        assert_eq!(app.cards_remaining() + on_table, app.shoe_size, "a full shoe apart from the table");
+        assert!(app.true_count().abs() < 1.0, "the count starts over, with only the table's cards seen");
+    }
+
+    #[test]
+    fn the_deal_animation_reveals_cards_in_casino_order() {
+        use Suit::*;
+        let mut app = scripted(vec![n(10, Spades), n(7, Clubs), n(9, Hearts), n(10, Diamonds)]);
+        let player = DealTarget::Player(0);
+        let mut seen = Vec::new();
+        for shown in 0..=4 {
+            app.deal_animation = Some((shown, Instant::now()));
+            seen.push((app.visible_cards(player), app.visible_cards(DealTarget::Dealer)));
+        }
+        assert_eq!(seen, [(0, 0), (1, 0), (1, 1), (2, 1), (2, 2)]);
+        app.deal_animation = None;
+        assert!(app.visible_cards(player) >= app.player_hands[0].len());
+        assert_eq!(hand_to_string(&app.player_hands[0], app.rules.value_rules), "10♠ 9♥ (19)");
+        assert_eq!(hand_to_string(&app.dealer_hand, app.rules.value_rules), "7♣ 10♦ (17)");
+    }
+
+    #[test]
+    fn goals_succeed_on_the_target_and_fail_below_the_floor() {
+        use Suit::*;
+        let winning = vec![n(10, Spades), n(7, Clubs), n(9, Hearts), n(10, Diamonds)];
+        let mut app = scripted(winning);
+        app.win_condition = WinCondition::TargetBankroll(110);
+        app.stand().unwrap();
+        assert_eq!(app.goal_result, Some(true));
+
+        let losing = vec![n(10, Spades), n(10, Clubs), n(6, Hearts), n(9, Diamonds)];
+        let mut app = scripted(losing);
+        app.win_condition = WinCondition::BankrollFloor(95);
+        app.stand().unwrap();
+        assert_eq!(app.total_money, 90);
+        assert_eq!(app.goal_result, Some(false));
+    }
+
+    #[test]
+    fn dealer_distribution_matches_published_figures() {
+        use Suit::*;
+        // Infinite-deck stand-on-soft-17 figures; eight decks should land within a point of them
+        let rules = RuleSet { num_decks: 8, ..RuleSet::default() };
+        let app = BlackjackApp::with_seed_and_rules(13, rules.clone());
+        let six = app.dealer_outcome_distribution(&n(6, Hearts));
+        assert!((six.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!((six[0] - 0.1654).abs() < 0.01, "{:?}", six);
+        assert!((six[5] - 0.4232).abs() < 0.01, "{:?}", six);
+        let two = app.dealer_outcome_distribution(&n(2, Hearts));
+        assert!((two[5] - 0.3536).abs() < 0.01, "{:?}", two);
+        let ten = app.dealer_outcome_distribution(&n(10, Hearts));
+        assert!((ten[3] - 0.3390).abs() < 0.01, "{:?}", ten);
+
+        // Hitting soft 17 turns some of those 17s into busts
+        let h17 = RuleSet { dealer_threshold: DealerThreshold { stand_hard: 17, stand_soft: 18 }, ..rules };
+        let app = BlackjackApp::with_seed_and_rules(13, h17);
+        let hits = app.dealer_outcome_distribution(&n(6, Hearts));
+        assert!(hits[0] < six[0] - 0.05 && hits[5] > six[5] + 0.01, "{:?}", hits);
+    }
+
+    #[test]
+    fn dealer_distribution_follows_the_shoe() {
+        use Suit::*;
+        // Only tens left: a 6 always goes to 16 and then busts
+        let mut app = scripted(vec![n(10, Spades), n(6, Clubs), n(9, Hearts), n(10, Diamonds), n(10, Clubs), n(10, Hearts)]);
+        let finishes = app.dealer_outcome_distribution(&app.dealer_hand[0].clone());
+        assert_eq!(finishes[5], 1.0);
+        app.rules.dealer_threshold.stand_hard = 16;
+        assert_eq!(app.dealer_outcome_distribution(&n(6, Clubs))[0], 1.0); // Stands on 16, counted in the first slot
+    }
+
+    #[test]
+    fn forgiving_first_hit_swaps_only_a_busting_card() {
+        use Suit::*;
+        let rules = RuleSet { forgiving_first_hit: true, ..RuleSet::default() };
+        let deal = vec![n(10, Spades), n(9, Hearts), n(6, Clubs), n(10, Diamonds)];
+        let mut app = scripted_with([deal.clone(), vec![card(Value::King, Clubs), n(3, Diamonds), n(5, Hearts)]].concat(), rules.clone());
+        app.hit().unwrap();
+        assert_eq!(BlackjackApp::calculate_hand_value(&app.player_hands[0], app.rules.value_rules), 19); // The nearest safe card
+        app.hit().unwrap();
+        assert!(matches!(app.game_state, GameState::GameOver(_))); // Only the first hit is forgiven
+
+        // A safe top card is dealt as it is, even with a better one further down
+        let mut app = scripted_with([deal, vec![n(2, Diamonds), n(5, Hearts), n(7, Clubs)]].concat(), rules);
+        app.hit().unwrap();
+        assert_eq!(BlackjackApp::calculate_hand_value(&app.player_hands[0], app.rules.value_rules), 18);
+    }
+
+    #[test]
+    fn doubling_for_less_stakes_and_pays_the_chosen_amount() {
+        use Suit::*;
+        let rules = RuleSet { double_for_less: true, ..RuleSet::default() };
+        let mut app = scripted_with(vec![n(5, Spades), n(9, Hearts), n(6, Clubs), n(8, Diamonds), n(10, Hearts)], rules);
+        app.double_amount = 4;
+        app.double_down().unwrap();
+        assert_eq!(app.player_bets[0], 14);
+        assert!(settled(&app));
+        assert_eq!(app.outcomes[0], Outcome::Won);
+        assert_eq!(app.total_money, 100 - 14 + 28);
+    }
+
+    #[test]
+    fn the_best_and_worst_rounds_are_the_extremes_of_the_session() {
+        let mut app = BlackjackApp::with_seed(8);
+        app.advance_delay = 0.0;
+        app.total_money = 1_000_000;
+        for round in 0..40 {
+            while matches!(app.game_state, GameState::PlayerTurn) {
+                if app.current_hand_value() < 12 && app.double_down().is_ok() {
+                    continue;
+                }
+                app.stand().unwrap();
+            }
+            app.wager = 10 * (round % 4 + 1); // Varied stakes so the extremes differ from a single bet
+            app.place_bet().unwrap();
+        }
+        let nets: Vec<i64> = app.round_history.iter().map(|record| record.net).collect();
+        assert_eq!(app.best_round(), *nets.iter().max().unwrap());
+        assert_eq!(app.worst_round(), *nets.iter().min().unwrap());
+        assert!(app.best_round() > 10 && app.worst_round() < -10);
+    }
+
+    #[test]
+    fn custom_dealer_thresholds_change_when_the_dealer_stops() {
+        use Suit::*;
+        let low = RuleSet { dealer_threshold: DealerThreshold { stand_hard: 16, stand_soft: 17 }, ..RuleSet::default() };
+        let mut app = scripted_with(vec![n(10, Spades), n(10, Hearts), n(9, Clubs), n(6, Diamonds), n(5, Spades)], low);
+        app.stand().unwrap();
+        assert_eq!(app.dealer_hand.len(), 2); // Stands on hard 16
+        assert_eq!(app.outcomes[0], Outcome::Won);
+
+        let hits_soft_17 = RuleSet { dealer_threshold: DealerThreshold { stand_hard: 17, stand_soft: 18 }, ..RuleSet::default() };
+        let mut app = scripted_with(vec![n(10, Spades), card(Value::Ace, Hearts), n(9, Clubs), n(6, Diamonds), n(2, Spades)], hits_soft_17);
+        app.stand().unwrap();
+        assert_eq!(BlackjackApp::calculate_hand_value(&app.dealer_hand, app.rules.value_rules), 19);
+        assert_eq!(app.outcomes[0], Outcome::Push);
+    }
+
+    #[test]
+    fn a_twenty_beaten_by_a_drawn_21_is_a_bad_beat() {
+        use Suit::*;
+        let mut app = scripted(vec![n(10, Spades), n(6, Hearts), n(10, Clubs), n(5, Diamonds), card(Value::King, Diamonds)]);
+        app.stand().unwrap();
+        assert_eq!(app.outcomes[0], Outcome::Lost);
+        assert!(app.round_history.last().unwrap().bad_beat);
+
+        let rules = app.rules.value_rules;
+        let twenty = [n(10, Spades), n(10, Clubs)];
+        assert!(!BlackjackApp::is_bad_beat(&twenty, &[card(Value::Ace, Hearts), card(Value::King, Diamonds)], rules)); // Nothing drawn
+        assert!(!BlackjackApp::is_bad_beat(&[n(10, Spades), n(7, Clubs)], &[n(6, Hearts), n(5, Diamonds), n(10, Clubs)], rules));
+    }
+
+    #[test]
+    fn the_remaining_composition_leaves_out_dealt_cards() {
+        let app = BlackjackApp::with_seed_and_rules(21, RuleSet { num_decks: 1, ..RuleSet::default() });
+        let mut expected = CompositionStats { high: 20, neutral: 12, low: 20 };
+        let gone = app.player_hands[0].iter().chain(app.dealer_hand.iter()).chain(app.burned.iter());
+        for card in gone {
+            match card.value {
+                Value::Ace | Value::Number(10) | Value::Jack | Value::Queen | Value::King => expected.high -= 1,
+                Value::Number(n) if n >= 7 => expected.neutral -= 1,
+                Value::Number(_) => expected.low -= 1,
+            }
+        }
+        let stats = app.remaining_composition();
+        assert_eq!((stats.high, stats.neutral, stats.low), (expected.high, expected.neutral, expected.low));
+        assert_eq!(stats.high + stats.neutral + stats.low, app.cards_remaining());
+    }
+
+    #[test]
+    fn a_hit_to_21_waits_for_stand_unless_auto_stand_is_on() {
+        use Suit::*;
+        let deal = vec![n(10, Spades), n(9, Hearts), n(6, Clubs), n(8, Diamonds), n(5, Hearts)];
+        let mut app = scripted(deal.clone());
+        app.hit().unwrap();
+        assert_eq!(app.current_hand_value(), 21);
+        assert!(matches!(app.game_state, GameState::PlayerTurn));
+        app.stand().unwrap();
+        assert_eq!(app.outcomes[0], Outcome::Won);
+
+        let mut app = scripted(deal);
+        app.auto_stand_21 = true;
+        app.hit().unwrap();
+        assert!(settled(&app));
+    }
+
+    #[test]
+    fn hands_per_hour_divides_rounds_by_elapsed_time() {
+        assert_eq!(BlackjackApp::hands_per_hour_over(30, Duration::from_secs(30 * 60)), 60.0);
+        assert_eq!(BlackjackApp::hands_per_hour_over(12, Duration::from_secs(0)), 0.0);
+        assert_eq!(BlackjackApp::hands_per_hour_over(0, Duration::from_secs(3600)), 0.0);
+    }
+
+    #[test]
+    fn rule_changes_move_the_estimated_edge_by_their_published_amounts() {
+        let base = RuleSet::default();
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        let hits_soft_17 = RuleSet { dealer_threshold: DealerThreshold { stand_hard: 17, stand_soft: 18 }, ..base.clone() };
+        assert!(close(hits_soft_17.estimated_edge() - base.estimated_edge(), 0.22));
+        let no_das = RuleSet { double_after_split: false, ..base.clone() };
+        assert!(close(no_das.estimated_edge() - base.estimated_edge(), 0.14));
+        let ten_eleven = RuleSet { double_restriction: DoubleRule::TenEleven, ..base.clone() };
+        assert!(close(ten_eleven.estimated_edge() - base.estimated_edge(), 0.26));
+        let single_deck = RuleSet { num_decks: 1, ..base.clone() };
+        assert!(close(base.estimated_edge() - single_deck.estimated_edge(), 0.54));
+    }
+
+    #[test]
+    fn giving_up_loses_every_stake_without_the_dealer_drawing() {
+        use Suit::*;
+        let mut app = scripted(vec![n(8, Spades), n(6, Hearts), n(8, Clubs), n(5, Diamonds), n(2, Spades), n(3, Clubs), n(10, Hearts)]);
+        app.split().unwrap();
+        app.abandon_round();
+        assert!(settled(&app));
+        assert_eq!(app.outcomes, vec![Outcome::Lost, Outcome::Lost]);
+        assert_eq!(app.total_money, 80);
+        assert_eq!(app.dealer_hand.len(), 2);
+    }
+
+    #[test]
+    fn a_bankrupt_practice_bankroll_is_topped_up() {
+        use Suit::*;
+        let losing = vec![n(10, Spades), n(10, Clubs), n(6, Hearts), n(9, Diamonds)];
+        let mut app = scripted(losing.clone());
+        app.total_money = 0; // Everything is on the table
+        app.auto_topup = Some(250);
+        app.stand().unwrap();
+        assert_eq!(app.total_money, 250);
+
+        let mut app = scripted(losing);
+        app.total_money = 0;
+        app.stand().unwrap();
+        assert_eq!(app.total_money, 0);
+        assert_eq!(app.place_bet(), Err(GameError::InsufficientFunds));
+    }
+
+    #[test]
+    fn the_dealer_summary_lists_the_deal_then_each_hit() {
+        use Suit::*;
+        let cards = vec![n(10, Spades), n(6, Hearts), n(9, Clubs), n(10, Diamonds), n(7, Spades)];
+        let mut app = scripted(cards.clone());
+        app.stand().unwrap();
+        assert_eq!(app.dealer_play_summary(), "6♥ + 10♦ = 16, hit 7♠ = 23, bust");
+
+        // Without a hole card the second card arrives on the dealer's turn, but it isn't a hit
+        let mut app = scripted_with(cards, RuleSet { hole_card: HoleCard::EuropeanNHC, ..RuleSet::default() });
+        app.stand().unwrap();
+        assert_eq!(app.dealer_play_summary(), "6♥ + 10♦ = 16, hit 7♠ = 23, bust");
+    }
+
+    #[test]
+    fn a_no_split_tens_table_still_splits_eights() {
+        use Suit::*;
+        let rules = RuleSet { allow_split_tens: false, ..RuleSet::default() };
+        let kings = vec![card(Value::King, Spades), n(9, Hearts), card(Value::King, Clubs), n(8, Diamonds), n(2, Spades), n(3, Clubs)];
+        let mut app = scripted_with(kings, rules.clone());
+        assert!(!app.can_split_now());
+        assert_eq!(app.split(), Err(GameError::IllegalAction));
+
+        let eights = vec![n(8, Spades), n(9, Hearts), n(8, Clubs), n(10, Diamonds), n(2, Spades), n(3, Clubs)];
+        let mut app = scripted_with(eights, rules);
+        app.split().unwrap();
+        assert_eq!(app.player_hands.len(), 2);
+    }
+
+    #[test]
+    fn the_deal_check_catches_missing_and_repeated_cards() {
+        let mut app = BlackjackApp::with_seed_and_rules(4, RuleSet { num_decks: 1, ..RuleSet::default() });
+        assert!(app.check_deal().is_ok());
+
+        let copy = app.dealer_hand[0].clone();
+        app.deck[0] = copy; // Two of the upcard in a single deck
+        assert!(app.check_deal().unwrap_err().contains("appears more than 1 times"));
+
+        let mut app = BlackjackApp::with_seed(4);
+        app.dealer_hand.pop();
+        assert_eq!(app.check_deal(), Err(String::from("dealer was dealt 1 cards")));
+    }
+
+    #[test]
+    fn a_hard_12_can_reach_each_total_left_in_the_shoe() {
+        use Suit::*;
+        let app = scripted(vec![
+            n(10, Spades),
+            n(9, Hearts),
+            n(2, Clubs),
+            n(8, Diamonds),
+            card(Value::Ace, Spades),
+            n(5, Clubs),
+            n(9, Diamonds),
+            card(Value::King, Hearts),
+        ]);
+        assert_eq!(app.reachable_totals(), vec![(13, 0.25), (17, 0.25), (21, 0.25), (22, 0.25)]);
+        let bust: f64 = app.reachable_totals().iter().filter(|&&(total, _)| total > 21).map(|&(_, chance)| chance).sum();
+        assert_eq!(bust, 0.25);
+    }
+
+    #[test]
+    fn a_dealer_22_pushes_only_under_the_push_22_rule() {
+        use Suit::*;
+        let cards = vec![n(10, Spades), n(6, Hearts), card(Value::Queen, Clubs), n(6, Diamonds), card(Value::King, Spades)];
+        let mut app = scripted_with(cards.clone(), RuleSet { dealer_22_pushes: true, ..RuleSet::default() });
+        app.stand().unwrap();
+        assert_eq!(app.outcomes[0], Outcome::Push);
+        assert_eq!(app.total_money, 100);
+
+        let mut app = scripted(cards);
+        app.stand().unwrap();
+        assert_eq!(app.outcomes[0], Outcome::Won);
+        assert_eq!(app.total_money, 110);
+    }
+
+    #[test]
+    fn recent_bets_keep_the_last_few_distinct_amounts() {
+        let mut app = BlackjackApp::undealt(5, RuleSet::default());
+        app.advance_delay = 0.0;
+        app.total_money = 10_000;
+        for &amount in &[10, 20, 10, 30, 40, 50, 60] {
+            app.wager = amount;
+            app.place_bet().unwrap();
+            while matches!(app.game_state, GameState::PlayerTurn) {
+                app.stand().unwrap();
+            }
+        }
+        assert_eq!(app.recent_bets, vec![10, 30, 40, 50, 60]);
+    }
+
+    #[test]
+    fn a_settlement_hook_can_turn_ties_into_losses() {
+        use Suit::*;
+        let ties_lose: SettlementHook = Box::new(|hand, dealer| {
+            let value = |cards: &[Card]| BlackjackApp::calculate_hand_value(cards, ValueRules::default());
+            if value(hand) == value(dealer) {
+                Some(Outcome::Lost)
+            } else {
+                None
+            }
+        });
+        let mut app = scripted(vec![n(10, Spades), n(9, Hearts), n(9, Clubs), card(Value::King, Diamonds)]);
+        app.set_settlement_hook(ties_lose);
+        app.stand().unwrap();
+        assert_eq!(app.outcomes[0], Outcome::Lost);
+        assert_eq!(app.total_money, 90);
+
+        let mut app = scripted(vec![n(10, Spades), n(9, Hearts), n(10, Clubs), card(Value::King, Diamonds)]);
+        app.set_settlement_hook(Box::new(|_, _| None));
+        app.stand().unwrap();
+        assert_eq!(app.outcomes[0], Outcome::Won); // None leaves normal settlement alone
+    }
+
+    #[test]
+    fn a_hidden_total_comes_back_once_the_round_is_settled() {
+        use Suit::*;
+        let mut app = scripted(vec![n(10, Spades), n(7, Clubs), n(9, Hearts), n(10, Diamonds)]);
+        assert!(app.should_show_player_total());
+        app.show_player_total = false;
+        assert!(!app.should_show_player_total());
+        assert_eq!(app.player_total_label(0), "?");
+        app.stand().unwrap();
+        assert!(app.should_show_player_total());
+        assert_ne!(app.player_total_label(0), "?");
+    }
+
+    #[test]
+    fn a_replay_follows_rule_changes_and_forced_upcards() {
+        let mut app = BlackjackApp::with_seed(17);
+        app.advance_delay = 0.0;
+        app.wager = 2; // Small enough to never go broke
+        for round in 0..30 {
+            while matches!(app.game_state, GameState::PlayerTurn) {
+                app.stand().unwrap();
+            }
+            if round == 10 {
+                app.rules.num_decks = 2; // As the settings panel does it
+                app.needs_reshuffle = true;
+                app.rules.dealer_threshold.stand_soft = 18;
+            }
+            if round == 20 {
+                app.force_dealer_upcard(Some(Value::Ace));
+            }
+            app.place_bet().unwrap();
+        }
+        assert_eq!(app.shoe_size, 104);
+        assert_eq!(app.dealer_hand[0].value, Value::Ace);
+
+        let replayed = BlackjackApp::replay_events(17, RuleSet::default(), app.events()).unwrap();
+        assert!(replayed.rules == app.rules);
+        assert_eq!(replayed.forced_upcard, Some(Value::Ace));
+        assert_eq!(replayed.shoe_size, app.shoe_size);
+        assert_eq!(replayed.total_money, app.total_money);
+        assert_eq!(hand_to_string(&replayed.dealer_hand, replayed.rules.value_rules), hand_to_string(&app.dealer_hand, app.rules.value_rules));
+        assert_eq!(replayed.events().len(), app.events().len());
+    }
+
+    #[test]
+    fn the_low_shoe_warning_counts_down_to_the_cut_card() {
+        let mut app = BlackjackApp::with_seed(6);
+        assert_eq!(app.shoe_size, 312); // The cut card goes in 78 cards from the back
+        app.deck.truncate(88);
+        assert_eq!(app.cards_to_cut(), 10);
+        assert!(!app.low_shoe_warning());
+        app.deck.truncate(87);
+        assert!(app.low_shoe_warning(), "nine cards from the cut, 87 still in the shoe");
+    }
+
+    #[test]
+    fn a_stacked_deck_deals_its_cards_in_order() {
+        use Suit::*;
+        let app = scripted(vec![n(2, Spades), n(3, Hearts), n(4, Clubs), n(5, Diamonds), n(6, Spades)]);
+        let shorts = |hand: &[Card]| hand.iter().map(|c| c.short()).collect::<Vec<_>>();
+        assert_eq!(shorts(&app.player_hands[0]), vec!["2♠", "4♣"]);
+        assert_eq!(shorts(&app.dealer_hand), vec!["3♥", "5♦"]);
+        assert_eq!(shorts(app.peek_next(1)), vec!["6♠"]);
+        assert!(app.burned.is_empty());
+    }
+
+    #[test]
+    fn the_dealer_narrates_each_decision() {
+        use Suit::*;
+        let mut app = scripted(vec![n(10, Spades), card(Value::Ace, Hearts), n(9, Clubs), n(5, Diamonds), n(10, Clubs), n(2, Spades)]);
+        app.narrate_dealer = true;
+        app.stand().unwrap();
+        assert_eq!(app.dealer_narration, vec!["Dealer has soft 16, must hit", "Dealer has 16, must hit", "Dealer has 18, stands"]);
+    }
+
+    #[test]
+    fn the_total_at_risk_counts_splits_and_doubles() {
+        use Suit::*;
+        let mut app = scripted(vec![
+            n(8, Spades),
+            n(6, Hearts),
+            n(8, Clubs),
+            n(10, Diamonds),
+            n(3, Spades),
+            card(Value::King, Clubs),
+            n(2, Diamonds),
+            n(9, Hearts),
+        ]);
+        assert_eq!(app.total_at_risk(), 10);
+        app.split().unwrap();
+        assert_eq!(app.total_at_risk(), 20);
+        app.double_down().unwrap();
+        assert_eq!(app.current_hand, 1);
+        assert_eq!(app.total_at_risk(), 30);
+        assert_eq!(app.total_money, 70);
+    }
+
+    #[test]
+    fn the_four_color_theme_gives_every_suit_its_own_color() {
+        let colors: Vec<_> = [Suit::Hearts, Suit::Diamonds, Suit::Clubs, Suit::Spades].iter().map(|&suit| CardTheme::FourColor.suit_color(suit)).collect();
+        for (index, color) in colors.iter().enumerate() {
+            assert!(!colors[index + 1..].contains(color), "{:?} is used twice", color);
+        }
+        assert_eq!(CardTheme::Classic.suit_color(Suit::Diamonds), CardTheme::Classic.suit_color(Suit::Hearts));
+    }
+
+    #[test]
+    fn a_continued_session_keeps_its_stats() {
+        let storage = MemoryStorage::default();
+        assert!(matches!(BlackjackApp::starting_from(&storage).game_state, GameState::Betting));
+
+        let mut app = BlackjackApp::with_seed(23);
+        app.advance_delay = 0.0;
+        for _ in 0..6 {
+            while matches!(app.game_state, GameState::PlayerTurn) {
+                app.stand().unwrap();
+            }
+            app.place_bet().unwrap();
+        }
+        app.save_state(&storage).unwrap();
+
+        let mut continued = BlackjackApp::starting_from(&storage);
+        assert!(matches!(continued.game_state, GameState::Startup));
+        continued.continue_session(&storage);
+        assert_eq!(continued.rounds_played, 6);
+        assert_eq!(continued.round_history.len(), 6);
+        assert_eq!(continued.best_round(), app.best_round());
+        assert_eq!(continued.worst_round(), app.worst_round());
+        assert_eq!(continued.current_shoe_net(), app.current_shoe_net());
+        assert_eq!(continued.total_money, app.total_money);
+    }
+
+    #[test]
+    fn bets_snap_to_the_nearest_chip() {
+        let rules = RuleSet { chip_denomination: 5, ..RuleSet::default() };
+        assert_eq!(rules.snap_to_chips(7), 5);
+        assert_eq!(rules.snap_to_chips(8), 10);
+        assert_eq!(rules.snap_to_chips(2), 5); // Never less than one chip
+
+        let mut app = BlackjackApp::undealt(2, rules);
+        app.wager = 7;
+        app.place_bet().unwrap();
+        assert_eq!(app.player_bets[0], 5);
+        assert_eq!(app.total_money, 95);
+    }
+
+    #[test]
+    fn standing_instead_would_have_handed_the_dealer_the_bust_card() {
+        use Suit::*;
+        let mut app = scripted(vec![n(10, Spades), n(10, Hearts), n(6, Clubs), n(6, Diamonds), card(Value::King, Clubs), n(2, Spades)]);
+        app.hit().unwrap();
+        assert_eq!(app.outcomes[0], Outcome::Busted);
+        assert_eq!(app.counterfactual_stand(0), Some(Outcome::Won)); // The king would have busted the dealer's 16
+        assert_eq!(app.counterfactual_stand(1), None);
+    }
+
+    #[test]
+    fn a_bankroll_near_the_limit_saturates_instead_of_overflowing() {
+        use Suit::*;
+        let mut app = scripted(vec![n(10, Spades), n(7, Clubs), n(9, Hearts), n(10, Diamonds)]);
+        app.total_money = usize::MAX - 5;
+        app.stand().unwrap();
+        assert_eq!(app.outcomes[0], Outcome::Won);
+        assert_eq!(app.total_money, usize::MAX);
+    }
+
+    #[test]
+    fn the_quiz_deals_real_hands_and_grades_typed_totals() {
+        let mut quiz = QuizState::default();
+        let mut rng = StdRng::seed_from_u64(4);
+        quiz.next_hand(&mut rng);
+        assert!((2..=4).contains(&quiz.hand.len()));
+
+        quiz.hand = vec![card(Value::Ace, Suit::Spades), n(6, Suit::Hearts), n(9, Suit::Clubs)];
+        quiz.answer = String::from("26");
+        assert_eq!(quiz.grade(ValueRules::default()), Some(false));
+        quiz.answer = String::from(" 16 ");
+        assert_eq!(quiz.grade(ValueRules::default()), Some(true));
+        quiz.answer = String::from("sixteen");
+        assert_eq!(quiz.grade(ValueRules::default()), None); // Not counted as an attempt
+        assert_eq!((quiz.asked, quiz.correct), (2, 1));
+        assert_eq!(quiz.accuracy(), 0.5);
+    }
+
+    #[test]
+    fn the_shoe_count_shows_only_when_its_setting_allows() {
+        let mut app = BlackjackApp::with_seed(9);
+        app.advance_delay = 0.0;
+        assert!(app.should_show_shoe_count());
+        app.shoe_count_display = ShoeCountDisplay::BetweenRounds;
+        assert!(!app.should_show_shoe_count(), "hidden mid-round");
+        while matches!(app.game_state, GameState::PlayerTurn) {
+            app.stand().unwrap();
+        }
+        assert!(app.should_show_shoe_count());
+
+        app.shoe_count_display = ShoeCountDisplay::AfterReshuffle;
+        assert!(app.should_show_shoe_count(), "nothing discarded from this shoe yet");
+        app.place_bet().unwrap();
+        while matches!(app.game_state, GameState::PlayerTurn) {
+            app.stand().unwrap();
+        }
+        assert!(!app.should_show_shoe_count(), "the first round has gone to the discards");
+    }
+
+    #[test]
+    fn betting_with_the_count_raises_the_heat() {
+        fn play(bet_with_count: bool) -> f64 {
+            let mut app = BlackjackApp::undealt(31, RuleSet::default());
+            app.advance_delay = 0.0;
+            app.total_money = 100_000;
+            for _ in 0..10 {
+                app.wager = if bet_with_count { (50.0 + app.true_count() * 20.0).max(1.0) as usize } else { 10 };
+                app.place_bet().unwrap();
+                while matches!(app.game_state, GameState::PlayerTurn) {
+                    app.stand().unwrap();
+                }
+            }
+            app.heat_level()
+        }
+        assert!(play(true) > 0.9);
+        assert_eq!(play(false), 0.0);
+    }
+
+    #[test]
+    fn the_beginner_assist_splits_eights_it_can_afford() {
+        use Suit::*;
+        let eights = vec![n(8, Spades), n(9, Hearts), n(8, Clubs), n(10, Diamonds), n(2, Spades), n(3, Clubs)];
+        let mut app = scripted(eights.clone());
+        app.auto_split_aces_eights = true;
+        app.auto_split_pairs(); // As at the start of the player's turn
+        assert_eq!(app.player_hands.len(), 2);
+        assert!(app.assist_note.is_some());
+
+        let mut app = scripted(eights);
+        app.auto_split_aces_eights = true;
+        app.total_money = 5; // Less than the second bet
+        app.auto_split_pairs();
+        assert_eq!(app.player_hands.len(), 1);
+    }
+
+    #[test]
+    fn naturals_pay_three_to_two_rounded_up_to_a_chip() {
+        let ones = RuleSet::default();
+        assert_eq!(ones.natural_winnings(10), 15);
+        assert_eq!(ones.natural_winnings(15), 23);
+        let fives = RuleSet { chip_denomination: 5, ..RuleSet::default() };
+        assert_eq!(fives.natural_winnings(5), 10); // Not even money
+        assert_eq!(fives.natural_winnings(10), 15);
+        assert_eq!(fives.natural_winnings(15), 25);
+    }
+
+    #[test]
+    fn a_bankroll_short_of_the_last_bet_is_not_broke() {
+        use Suit::*;
+        let mut app = scripted(vec![n(10, Spades), n(10, Clubs), n(6, Hearts), n(9, Diamonds)]);
+        app.wager = 50;
+        app.total_money = 20; // Short of the usual bet, but enough for a smaller one
+        app.win_condition = WinCondition::SurviveRounds(5);
+        app.auto_topup = Some(500);
+        app.stand().unwrap();
+        assert_eq!(app.total_money, 20);
+        assert_eq!(app.goal_result, None);
+    }
+
+    #[test]
+    fn the_cut_card_can_come_out_during_the_opening_deal() {
+        let mut app = BlackjackApp::with_seed(12);
+        app.advance_delay = 0.0;
+        while matches!(app.game_state, GameState::PlayerTurn) {
+            app.stand().unwrap();
+        }
+        let excess = app.deck.len() - (78 + 3); // Leave three cards before the cut card of a six-deck shoe
+        let dealt: Vec<Card> = app.deck.drain(..excess).collect();
+        app.discards.extend(dealt);
+        app.needs_reshuffle = false;
+        app.place_bet().unwrap();
+        assert_eq!(app.cards_remaining(), 77);
+        assert!(app.needs_reshuffle, "the next round starts from a new shoe");
     }
 }